@@ -0,0 +1,13 @@
+# Upload a collection WASM to the launchpad in chunks and commit it as a new version.
+# Usage: scripts/icrc7_launchpad_wasm.sh <version> [wasm_path]
+VERSION=$1
+WASM=${2:-wasm/icrc7.wasm.gz}
+CHUNK_DIR=$(mktemp -d)
+
+split -b 500000 -d "$WASM" "$CHUNK_DIR/chunk_"
+for chunk in "$CHUNK_DIR"/chunk_*; do
+  echo "(\"$VERSION\", blob \"$(xxd -p "$chunk" | tr -d '\n' | sed 's/../\\&/g')\")" > "$CHUNK_DIR/arg"
+  dfx canister call icrc7_launchpad upload_wasm_chunk --argument-file "$CHUNK_DIR/arg"
+done
+dfx canister call icrc7_launchpad commit_wasm_version "(\"$VERSION\", null)"
+rm -rf "$CHUNK_DIR"
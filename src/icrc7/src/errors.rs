@@ -17,6 +17,9 @@ pub enum TransferError {
 pub enum BurnError {
     Unauthorized,
     NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
 }
@@ -27,6 +30,9 @@ pub enum MintError {
     Unauthorized,
     TokenIdAlreadyExist,
     TokenIdMinimumLimit,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
 }
@@ -98,4 +104,52 @@ pub enum TransferFromError {
     Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
-}
\ No newline at end of file
+}
+
+// Deduplication Error
+
+/// Why a transaction with a `created_at_time` was rejected, shared by every
+/// update call that goes through the dedup index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxnDedupError {
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
+    // The dedup index is still being seeded by a state migration.
+    Migrating,
+}
+
+macro_rules! impl_from_txn_dedup_error {
+    ($($error:ident),*) => {
+        $(
+            impl From<TxnDedupError> for $error {
+                fn from(e: TxnDedupError) -> Self {
+                    match e {
+                        TxnDedupError::TooOld => Self::TooOld,
+                        TxnDedupError::CreatedInFuture { ledger_time } => {
+                            Self::CreatedInFuture { ledger_time }
+                        }
+                        TxnDedupError::Duplicate { duplicate_of } => {
+                            Self::Duplicate { duplicate_of }
+                        }
+                        TxnDedupError::Migrating => Self::GenericError {
+                            error_code: 8,
+                            message: "State Migration Pending".into(),
+                        },
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_txn_dedup_error!(
+    TransferError,
+    BurnError,
+    MintError,
+    ApproveTokenError,
+    ApproveCollectionError,
+    RevokeTokenApprovalError,
+    RevokeCollectionApprovalError,
+    TransferFromError
+);
@@ -15,6 +15,15 @@ pub fn owner_guard() -> Result<(), String> {
     }
 }
 
+#[inline(always)]
+pub fn self_guard() -> Result<(), String> {
+    if caller() == ic_cdk::id() {
+        Ok(())
+    } else {
+        Err(String::from("The caller is not the canister itself"))
+    }
+}
+
 #[inline(always)]
 pub fn authenticated_guard() -> Result<(), String> {
     if ic_cdk::caller() == Principal::anonymous() {
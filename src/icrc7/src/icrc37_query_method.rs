@@ -20,6 +20,7 @@ pub fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
         Some(Nat::from(
             s.borrow()
                 .approval_ledger_info
+                .get()
                 .max_approvals_per_token_or_collection,
         ))
     })
@@ -30,7 +31,7 @@ pub fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
 pub fn icrc37_max_revoke_approvals() -> Option<Nat> {
     STATE.with(|s| {
         Some(Nat::from(
-            s.borrow().approval_ledger_info.max_revoke_approvals,
+            s.borrow().approval_ledger_info.get().max_revoke_approvals,
         ))
     })
 }
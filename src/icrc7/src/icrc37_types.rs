@@ -66,6 +66,18 @@ impl Default for LedgerInfo {
     }
 }
 
+impl Storable for LedgerInfo {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
 pub struct ApprovalInfo {
     pub from_subaccount: Option<Subaccount>,
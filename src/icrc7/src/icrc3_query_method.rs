@@ -9,7 +9,13 @@ use icrc_ledger_types::icrc3::blocks::DataCertificate;
 // Returns all the supported block types.
 #[query]
 pub fn icrc3_supported_block_types() -> Vec<BlockType> {
-    STATE.with(|s| s.borrow().archive_ledger_info.supported_blocks.clone())
+    STATE.with(|s| {
+        s.borrow()
+            .archive_ledger_info
+            .get()
+            .supported_blocks
+            .clone()
+    })
 }
 
 // Listing all the canisters containing its blocks
@@ -142,9 +142,23 @@ pub struct TransactionRange {
     pub length: u128,
 }
 
+impl Storable for TransactionRange {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
 pub struct ArchiveLedgerInfo {
-    pub archives: BTreeMap<Principal, TransactionRange>,
+    // Archives listed before they moved to their own stable map. Only read by
+    // the migration that moves them there, `None` afterwards.
+    pub archives: Option<BTreeMap<Principal, TransactionRange>>,
     pub local_ledger_size: u128,
     pub supported_blocks: Vec<BlockType>,
     pub last_index: u128,
@@ -157,7 +171,7 @@ pub struct ArchiveLedgerInfo {
 impl Default for ArchiveLedgerInfo {
     fn default() -> Self {
         Self {
-            archives: BTreeMap::new(),
+            archives: None,
             local_ledger_size: 0,
             supported_blocks: vec![],
             last_index: 0,
@@ -169,11 +183,23 @@ impl Default for ArchiveLedgerInfo {
     }
 }
 
+impl Storable for ArchiveLedgerInfo {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 impl ArchiveLedgerInfo {
     pub fn new(setting: Option<ArchiveSetting>) -> Self {
         let setting = setting.unwrap_or(ArchiveSetting::default());
         Self {
-            archives: BTreeMap::new(),
+            archives: None,
             local_ledger_size: 0,
             last_index: 0,
             first_index: 0,
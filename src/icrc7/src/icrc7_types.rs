@@ -14,7 +14,13 @@ use crate::{
     icrc3_types::{Block, InitArchiveArg},
 };
 
+pub static TRANSACTION_MINT_OP: &str = "7mint";
+pub static TRANSACTION_BURN_OP: &str = "7burn";
 pub static TRANSACTION_TRANSFER_OP: &str = "7xfer";
+pub static TRANSACTION_APPROVE_OP: &str = "37appr";
+pub static TRANSACTION_APPROVE_COLLECTION_OP: &str = "37appr_coll";
+pub static TRANSACTION_REVOKE_OP: &str = "37revoke";
+pub static TRANSACTION_REVOKE_COLLECTION_OP: &str = "37revoke_coll";
 pub static TRANSACTION_TRANSFER_FROM_OP: &str = "37xfer";
 
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
@@ -342,6 +348,7 @@ pub struct MintArg {
     pub token_description: Option<String>,
     pub token_logo: Option<String>,
     pub extra_data: Option<Icrc7TokenMetadata>,
+    pub created_at_time: Option<u64>,
 }
 
 pub type MintResult = Result<u128, MintError>;
@@ -351,6 +358,7 @@ pub struct BurnArg {
     pub from_subaccount: Option<Subaccount>,
     pub token_id: u128,
     pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
 }
 
 pub type BurnResult = Result<u128, BurnError>;
@@ -391,3 +399,20 @@ pub struct ApprovalArg {
 }
 
 pub type SyncReceipt = Result<u32, InsertTransactionError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct MigrationStatus {
+    // Version of the state in stable memory.
+    pub version: u32,
+    // Version this release migrates the state to.
+    pub target_version: u32,
+    // The migration from `version` to the next version, if the state is not
+    // current yet.
+    pub pending: Option<String>,
+    // Whether the pending migration has started and continues on a timer.
+    pub in_progress: bool,
+    // Failed runs of the pending migration in a row, and why the last one
+    // failed. The timer stops after `MIGRATION_MAX_ATTEMPTS` of them.
+    pub failed_attempts: u32,
+    pub last_error: Option<String>,
+}
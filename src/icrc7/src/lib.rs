@@ -17,6 +17,7 @@ pub mod candid_file_generator;
 pub mod guards;
 pub mod errors;
 pub mod archive;
+pub mod migration;
 
 use icrc7_types::*;
 
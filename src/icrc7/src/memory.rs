@@ -1,14 +1,18 @@
 use crate::{
-    icrc37_types::{CollectionApprovalInfo, TokenApprovalInfo, UserAccount},
+    icrc37_types::{CollectionApprovalInfo, LedgerInfo, TokenApprovalInfo, UserAccount},
+    icrc3_types::{ArchiveLedgerInfo, TransactionRange},
     icrc7_types::Transaction,
-    state::{Icrc7Token, MEMORY_MANAGER},
+    migration::MigrationState,
+    state::{CollectionConfig, Icrc7Token, LedgerMetadata, OwnerTokenKey, MEMORY_MANAGER},
 };
+use candid::Principal;
 use ic_stable_structures::{
     memory_manager::{MemoryId, VirtualMemory},
-    DefaultMemoryImpl, StableBTreeMap,
+    DefaultMemoryImpl, StableBTreeMap, StableCell,
 };
 
-// A memory for upgrades, where data from the heap can be serialized/deserialized.
+// A memory for upgrades. Releases that kept the state on the heap serialized
+// it here; it is now only read once to migrate such a state into stable cells.
 const UPGRADES: MemoryId = MemoryId::new(0);
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -33,3 +37,63 @@ pub fn get_collection_approvals_memory(
 ) -> StableBTreeMap<UserAccount, CollectionApprovalInfo, Memory> {
     StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))))
 }
+
+pub fn get_owner_index_memory() -> StableBTreeMap<OwnerTokenKey, (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))))
+}
+
+pub fn get_dedup_index_memory() -> StableBTreeMap<[u8; 32], u128, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))))
+}
+
+pub fn get_dedup_expiry_memory() -> StableBTreeMap<(u64, [u8; 32]), (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))))
+}
+
+pub fn get_config_memory() -> StableCell<CollectionConfig, Memory> {
+    StableCell::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        CollectionConfig::default(),
+    )
+    .expect("failed to init collection config")
+}
+
+pub fn get_ledger_metadata_memory() -> StableCell<LedgerMetadata, Memory> {
+    StableCell::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        LedgerMetadata::default(),
+    )
+    .expect("failed to init ledger metadata")
+}
+
+pub fn get_approval_ledger_info_memory() -> StableCell<LedgerInfo, Memory> {
+    StableCell::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        LedgerInfo::default(),
+    )
+    .expect("failed to init approval ledger info")
+}
+
+pub fn get_archive_ledger_info_memory() -> StableCell<ArchiveLedgerInfo, Memory> {
+    StableCell::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        ArchiveLedgerInfo::default(),
+    )
+    .expect("failed to init archive ledger info")
+}
+
+pub fn get_migration_memory() -> StableCell<MigrationState, Memory> {
+    StableCell::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        MigrationState::default(),
+    )
+    .expect("failed to init migration state")
+}
+
+pub fn get_archives_memory() -> StableBTreeMap<Principal, TransactionRange, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))))
+}
+
+pub fn get_sync_pending_txn_ids_memory() -> StableBTreeMap<u128, (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))))
+}
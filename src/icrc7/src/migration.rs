@@ -0,0 +1,721 @@
+use std::{borrow::Cow, time::Duration};
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Memory, Storable};
+use icrc_ledger_types::icrc1::account::Account;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    icrc37_types::LedgerInfo,
+    icrc3_types::ArchiveLedgerInfo,
+    icrc7_types::MigrationStatus,
+    state::{CollectionConfig, LedgerMetadata, OwnerTokenKey, State, STATE},
+};
+
+/// Version of the state layout this release reads and writes. Every bump needs
+/// a migration from the previous version in `MIGRATIONS`.
+pub const STATE_VERSION: u32 = 5;
+// Entries a chunked migration handles per call, so each stays well within the
+// instruction limit of `post_upgrade` and of a timer.
+pub const MIGRATION_BATCH_SIZE: usize = 1_000;
+const MIGRATION_INTERVAL: Duration = Duration::from_secs(1);
+// Failed runs of a step before the timer gives up, `resume_migration` retries it.
+pub const MIGRATION_MAX_ATTEMPTS: u32 = 5;
+
+/// Version of the state in stable memory, and where the migration to the next
+/// version stopped. A canister whose state has never been versioned decodes
+/// the default, version 0.
+#[derive(CandidType, Serialize, Deserialize, Clone, Default)]
+pub struct MigrationState {
+    pub version: u32,
+    // Storable bytes of the last key migrated, for migrations over a map.
+    pub cursor: Option<Vec<u8>>,
+    // Why the last run of the pending step trapped, cleared once a run succeeds.
+    pub failure: Option<MigrationFailure>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone)]
+pub struct MigrationFailure {
+    pub message: String,
+    // Failed runs in a row.
+    pub attempts: u32,
+}
+
+impl Storable for MigrationState {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+enum MigrationStep {
+    Done,
+    // More entries remain after the key with these Storable bytes.
+    Continue(Vec<u8>),
+}
+
+struct Migration {
+    description: &'static str,
+    run: fn(&mut State, Option<Vec<u8>>) -> MigrationStep,
+}
+
+// `MIGRATIONS[v]` migrates a version `v` state to version `v + 1`.
+const MIGRATIONS: [Migration; STATE_VERSION as usize] = [
+    Migration {
+        description: "move the heap state into stable cells",
+        run: migrate_heap_state,
+    },
+    Migration {
+        description: "index tokens by owner",
+        run: index_token_owners,
+    },
+    Migration {
+        description: "seed the dedup index from the local ledger",
+        run: seed_dedup_index,
+    },
+    Migration {
+        description: "move the archive list and sync-pending ids out of the cells",
+        run: move_ledger_lists,
+    },
+    Migration {
+        description: "count the token holders",
+        run: count_holders,
+    },
+];
+
+/// Runs one step of the pending migration, if any, and returns whether the
+/// state is now at `STATE_VERSION`.
+pub fn run_migration_step() -> bool {
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        let MigrationState {
+            version, cursor, ..
+        } = s.migration.get().clone();
+        let Some(migration) = MIGRATIONS.get(version as usize) else {
+            return true;
+        };
+        let migration_state = match (migration.run)(&mut s, cursor) {
+            MigrationStep::Done => MigrationState {
+                version: version + 1,
+                cursor: None,
+                failure: None,
+            },
+            MigrationStep::Continue(cursor) => MigrationState {
+                version,
+                cursor: Some(cursor),
+                failure: None,
+            },
+        };
+        let done = migration_state.version >= STATE_VERSION;
+        s.migration
+            .set(migration_state)
+            .expect("failed to write migration state");
+        done
+    })
+}
+
+/// Restarts a migration whose timer gave up after `MIGRATION_MAX_ATTEMPTS`
+/// failed runs. Returns false when the timer is still running or no migration
+/// is pending.
+pub fn resume_migration() -> bool {
+    let stopped = STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        let mut migration_state = s.migration.get().clone();
+        let stopped = migration_state
+            .failure
+            .as_ref()
+            .is_some_and(|failure| failure.attempts >= MIGRATION_MAX_ATTEMPTS);
+        if !stopped || s.is_migrated() {
+            return false;
+        }
+        migration_state.failure = None;
+        s.migration
+            .set(migration_state)
+            .expect("failed to write migration state");
+        true
+    });
+    if stopped {
+        set_migration_timer();
+    }
+    stopped
+}
+
+/// Runs migrations until the state is current or a step has to wait for the
+/// next timer.
+pub fn run_migrations() {
+    let version = STATE.with(|s| s.borrow().migration.get().version);
+    if version > STATE_VERSION {
+        ic_cdk::trap(&format!(
+            "state version {} is newer than this release ({})",
+            version, STATE_VERSION
+        ));
+    }
+    loop {
+        let version = STATE.with(|s| s.borrow().migration.get().version);
+        if run_migration_step() {
+            return;
+        }
+        // A chunked migration made progress without finishing: continue on a
+        // timer so the remaining entries get a fresh instruction budget.
+        if version == STATE.with(|s| s.borrow().migration.get().version) {
+            set_migration_timer();
+            return;
+        }
+    }
+}
+
+fn set_migration_timer() {
+    ic_cdk_timers::set_timer(MIGRATION_INTERVAL, || {
+        ic_cdk::spawn(migration_task());
+    });
+}
+
+// Runs the next step in a call to the canister itself: a step that traps only
+// rolls back that call, and its failure is recorded here.
+async fn migration_task() {
+    match ic_cdk::call::<(), (bool,)>(ic_cdk::id(), "migration_step", ()).await {
+        Ok((true,)) => {}
+        Ok((false,)) => set_migration_timer(),
+        Err((code, message)) => {
+            let attempts = STATE.with(|s| {
+                record_migration_failure(&mut s.borrow_mut(), format!("{:?}: {}", code, message))
+            });
+            if attempts < MIGRATION_MAX_ATTEMPTS {
+                set_migration_timer();
+            }
+        }
+    }
+}
+
+// Records a failed run of the pending step and returns the failed runs in a row.
+pub fn record_migration_failure(state: &mut State, message: String) -> u32 {
+    let mut migration_state = state.migration.get().clone();
+    let attempts = migration_state
+        .failure
+        .map_or(0, |failure| failure.attempts)
+        + 1;
+    migration_state.failure = Some(MigrationFailure { message, attempts });
+    state
+        .migration
+        .set(migration_state)
+        .expect("failed to write migration state");
+    attempts
+}
+
+pub fn migration_status(state: &State) -> MigrationStatus {
+    let MigrationState {
+        version,
+        cursor,
+        failure,
+    } = state.migration.get().clone();
+    MigrationStatus {
+        version,
+        target_version: STATE_VERSION,
+        pending: MIGRATIONS
+            .get(version as usize)
+            .map(|migration| migration.description.to_string()),
+        in_progress: cursor.is_some(),
+        failed_attempts: failure.as_ref().map_or(0, |failure| failure.attempts),
+        last_error: failure.map(|failure| failure.message),
+    }
+}
+
+// The heap state of releases that serialized it into the upgrades memory in
+// `pre_upgrade`. Only the fields that are not in stable structures.
+#[derive(Deserialize)]
+struct LegacyState {
+    minting_authority: Option<Account>,
+    icrc7_symbol: String,
+    icrc7_name: String,
+    icrc7_description: Option<String>,
+    icrc7_logo: Option<String>,
+    icrc7_total_supply: u128,
+    icrc7_supply_cap: Option<u128>,
+    icrc7_max_query_batch_size: Option<u16>,
+    icrc7_max_update_batch_size: Option<u16>,
+    icrc7_max_take_value: Option<u128>,
+    icrc7_default_take_value: Option<u128>,
+    icrc7_max_memo_size: Option<u32>,
+    icrc7_atomic_batch_transfers: Option<bool>,
+    tx_window: Option<u64>,
+    permitted_drift: Option<u64>,
+    txn_count: u128,
+    next_token_id: u128,
+    approval_ledger_info: LedgerInfo,
+    archive_ledger_info: ArchiveLedgerInfo,
+    archive_log_canister: Option<Principal>,
+    sync_pending_txn_ids: Option<Vec<u128>>,
+    archive_txn_count: u128,
+}
+
+// v0 -> v1: moves the legacy heap state into the stable cells and clears it.
+// A canister without a legacy state, e.g. one installed with stable cells but
+// before versioning, has nothing to move.
+fn migrate_heap_state(state: &mut State, _cursor: Option<Vec<u8>>) -> MigrationStep {
+    let memory = crate::memory::get_upgrades_memory();
+    if memory.size() == 0 {
+        return MigrationStep::Done;
+    }
+
+    // Read the length of the state bytes.
+    let mut state_len_bytes = [0; 4];
+    memory.read(0, &mut state_len_bytes);
+    let state_len = u32::from_le_bytes(state_len_bytes) as usize;
+    if state_len == 0 {
+        return MigrationStep::Done;
+    }
+
+    // Read the bytes
+    let mut state_bytes = vec![0; state_len];
+    memory.read(4, &mut state_bytes);
+
+    let legacy: LegacyState =
+        ciborium::de::from_reader(&*state_bytes).expect("failed to decode legacy state");
+    state
+        .config
+        .set(CollectionConfig {
+            minting_authority: legacy.minting_authority,
+            icrc7_symbol: legacy.icrc7_symbol,
+            icrc7_name: legacy.icrc7_name,
+            icrc7_description: legacy.icrc7_description,
+            icrc7_logo: legacy.icrc7_logo,
+            icrc7_supply_cap: legacy.icrc7_supply_cap,
+            icrc7_max_query_batch_size: legacy.icrc7_max_query_batch_size,
+            icrc7_max_update_batch_size: legacy.icrc7_max_update_batch_size,
+            icrc7_max_take_value: legacy.icrc7_max_take_value,
+            icrc7_default_take_value: legacy.icrc7_default_take_value,
+            icrc7_max_memo_size: legacy.icrc7_max_memo_size,
+            icrc7_atomic_batch_transfers: legacy.icrc7_atomic_batch_transfers,
+            tx_window: legacy.tx_window,
+            permitted_drift: legacy.permitted_drift,
+        })
+        .expect("failed to write collection config");
+    state
+        .ledger_metadata
+        .set(LedgerMetadata {
+            icrc7_total_supply: legacy.icrc7_total_supply,
+            txn_count: legacy.txn_count,
+            next_token_id: legacy.next_token_id,
+            archive_log_canister: legacy.archive_log_canister,
+            sync_pending_txn_ids: legacy.sync_pending_txn_ids,
+            archive_txn_count: legacy.archive_txn_count,
+            holders_count: None,
+        })
+        .expect("failed to write ledger metadata");
+    state
+        .approval_ledger_info
+        .set(legacy.approval_ledger_info)
+        .expect("failed to write approval ledger info");
+    state
+        .archive_ledger_info
+        .set(legacy.archive_ledger_info)
+        .expect("failed to write archive ledger info");
+
+    memory.write(0, &0u32.to_le_bytes());
+    MigrationStep::Done
+}
+
+// v1 -> v2: adds every token to the owner index.
+fn index_token_owners(state: &mut State, cursor: Option<Vec<u8>>) -> MigrationStep {
+    let prev = cursor.map(|bytes| u128::from_bytes(Cow::Owned(bytes)));
+    match state.index_token_owners(prev, MIGRATION_BATCH_SIZE) {
+        Some(last) => MigrationStep::Continue(last.to_bytes().into_owned()),
+        None => MigrationStep::Done,
+    }
+}
+
+// v2 -> v3: adds the transfers still in the local ledger to the dedup index.
+fn seed_dedup_index(state: &mut State, cursor: Option<Vec<u8>>) -> MigrationStep {
+    let prev = cursor.map(|bytes| u128::from_bytes(Cow::Owned(bytes)));
+    let current_time = ic_cdk::api::time();
+    match state.seed_dedup_index(prev, MIGRATION_BATCH_SIZE, current_time) {
+        Some(last) => MigrationStep::Continue(last.to_bytes().into_owned()),
+        None => MigrationStep::Done,
+    }
+}
+
+// v3 -> v4: moves the archive list and the sync-pending transaction ids out of
+// the cells, which then only keep fixed-size fields. Both lists are short, an
+// archive holds many transactions and the ids cover at most one archive batch.
+fn move_ledger_lists(state: &mut State, _cursor: Option<Vec<u8>>) -> MigrationStep {
+    let archives = state.archive_ledger_info.get().archives.clone();
+    for (canister_id, range) in archives.unwrap_or_default() {
+        state.archives.insert(canister_id, range);
+    }
+    state.update_archive_ledger_info(|info| info.archives = None);
+    let txn_ids = state.ledger_metadata.get().sync_pending_txn_ids.clone();
+    if txn_ids.is_some() {
+        state.set_sync_pending_txn_ids(txn_ids);
+    }
+    state.update_ledger_metadata(|meta| meta.sync_pending_txn_ids = None);
+    MigrationStep::Done
+}
+
+// v4 -> v5: seeds the holder counter from the owner index, for states that
+// were indexed before `index_token_owners` kept the counter.
+fn count_holders(state: &mut State, cursor: Option<Vec<u8>>) -> MigrationStep {
+    let prev = cursor.map(|bytes| OwnerTokenKey::from_bytes(Cow::Owned(bytes)));
+    match state.count_holders(prev, MIGRATION_BATCH_SIZE) {
+        Some(last) => MigrationStep::Continue(last.to_bytes().into_owned()),
+        None => MigrationStep::Done,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use icrc_ledger_types::icrc::generic_value::Hash;
+
+    use ic_stable_structures::writer::Writer;
+
+    use super::*;
+    use crate::{
+        icrc3_types::{ArchiveSetting, BlockType, TransactionRange},
+        icrc7_types::Transaction,
+        state::Icrc7Token,
+        utils::{account_transformer, txn_dedup_hash},
+    };
+
+    // `ArchiveLedgerInfo` as written to its cell before v4, and as serialized
+    // with the heap state before v1.
+    #[derive(CandidType, Serialize)]
+    struct V3ArchiveLedgerInfo {
+        archives: BTreeMap<Principal, TransactionRange>,
+        local_ledger_size: u128,
+        supported_blocks: Vec<BlockType>,
+        last_index: u128,
+        first_index: u128,
+        is_cleaning: bool,
+        latest_hash: Option<Hash>,
+        setting: ArchiveSetting,
+    }
+
+    // The heap state as serialized by `pre_upgrade` before v1.
+    #[derive(Serialize)]
+    struct V0State {
+        minting_authority: Option<Account>,
+        icrc7_symbol: String,
+        icrc7_name: String,
+        icrc7_description: Option<String>,
+        icrc7_logo: Option<String>,
+        icrc7_total_supply: u128,
+        icrc7_supply_cap: Option<u128>,
+        icrc7_max_query_batch_size: Option<u16>,
+        icrc7_max_update_batch_size: Option<u16>,
+        icrc7_max_take_value: Option<u128>,
+        icrc7_default_take_value: Option<u128>,
+        icrc7_max_memo_size: Option<u32>,
+        icrc7_atomic_batch_transfers: Option<bool>,
+        tx_window: Option<u64>,
+        permitted_drift: Option<u64>,
+        txn_count: u128,
+        next_token_id: u128,
+        approval_ledger_info: LedgerInfo,
+        archive_ledger_info: V3ArchiveLedgerInfo,
+        archive_log_canister: Option<Principal>,
+        sync_pending_txn_ids: Option<Vec<u128>>,
+        archive_txn_count: u128,
+    }
+
+    fn archive(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn range(start: u128, length: u128) -> TransactionRange {
+        TransactionRange { start, length }
+    }
+
+    fn account(id: u8) -> Account {
+        account_transformer(Account {
+            owner: Principal::from_slice(&[id]),
+            subaccount: None,
+        })
+    }
+
+    // Adds a token without indexing its owner, as before v2.
+    fn add_token(state: &mut State, token_id: u128, owner: Account) {
+        let token = Icrc7Token {
+            token_id,
+            token_name: format!("Token {}", token_id),
+            token_description: None,
+            token_logo: None,
+            token_owner: owner,
+            extra_data: BTreeMap::new(),
+        };
+        state.tokens.insert(token_id, token);
+    }
+
+    fn set_version(state: &mut State, version: u32) {
+        let mut migration_state = state.migration.get().clone();
+        migration_state.version = version;
+        state.migration.set(migration_state).unwrap();
+    }
+
+    fn transfer_hash(txn: &Transaction, caller: Account) -> [u8; 32] {
+        txn_dedup_hash(&txn.op, &caller, Some(txn.tid), txn.to, &txn.memo, txn.ts)
+    }
+
+    #[test]
+    fn v3_archive_ledger_info_decodes() {
+        let v3 = V3ArchiveLedgerInfo {
+            archives: BTreeMap::from([(archive(1), range(0, 10))]),
+            local_ledger_size: 5,
+            supported_blocks: vec![],
+            last_index: 15,
+            first_index: 10,
+            is_cleaning: false,
+            latest_hash: None,
+            setting: ArchiveSetting::default(),
+        };
+        let info = ArchiveLedgerInfo::from_bytes(Cow::Owned(Encode!(&v3).unwrap()));
+        let archives = info.archives.unwrap();
+        assert_eq!(archives.len(), 1);
+        assert_eq!(archives[&archive(1)].length, 10);
+        assert_eq!(info.first_index, 10);
+    }
+
+    #[test]
+    fn v0_heap_state_moves_into_the_cells() {
+        let v0 = V0State {
+            minting_authority: Some(account(1)),
+            icrc7_symbol: "ICFL".into(),
+            icrc7_name: "ICP Flower".into(),
+            icrc7_description: Some("Flowers".into()),
+            icrc7_logo: None,
+            icrc7_total_supply: 42,
+            icrc7_supply_cap: Some(100),
+            icrc7_max_query_batch_size: Some(50),
+            icrc7_max_update_batch_size: None,
+            icrc7_max_take_value: Some(20),
+            icrc7_default_take_value: Some(10),
+            icrc7_max_memo_size: Some(64),
+            icrc7_atomic_batch_transfers: Some(true),
+            tx_window: Some(3_600_000_000_000),
+            permitted_drift: None,
+            txn_count: 60,
+            next_token_id: 43,
+            approval_ledger_info: LedgerInfo {
+                max_approvals: 7,
+                ..LedgerInfo::default()
+            },
+            archive_ledger_info: V3ArchiveLedgerInfo {
+                archives: BTreeMap::from([(archive(1), range(0, 10))]),
+                local_ledger_size: 50,
+                supported_blocks: vec![],
+                last_index: 59,
+                first_index: 10,
+                is_cleaning: false,
+                latest_hash: None,
+                setting: ArchiveSetting::default(),
+            },
+            archive_log_canister: Some(archive(1)),
+            sync_pending_txn_ids: Some(vec![8, 9]),
+            archive_txn_count: 10,
+        };
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&v0, &mut bytes).unwrap();
+        let mut memory = crate::memory::get_upgrades_memory();
+        let mut writer = Writer::new(&mut memory, 0);
+        writer.write(&(bytes.len() as u32).to_le_bytes()).unwrap();
+        writer.write(&bytes).unwrap();
+
+        let mut state = State::default();
+        assert!(matches!(
+            migrate_heap_state(&mut state, None),
+            MigrationStep::Done
+        ));
+
+        let config = state.config.get();
+        assert_eq!(config.minting_authority, Some(account(1)));
+        assert_eq!(config.icrc7_symbol, "ICFL");
+        assert_eq!(config.icrc7_name, "ICP Flower");
+        assert_eq!(config.icrc7_description.as_deref(), Some("Flowers"));
+        assert_eq!(config.icrc7_supply_cap, Some(100));
+        assert_eq!(config.icrc7_max_query_batch_size, Some(50));
+        assert_eq!(config.icrc7_default_take_value, Some(10));
+        assert_eq!(config.icrc7_atomic_batch_transfers, Some(true));
+        assert_eq!(config.tx_window, Some(3_600_000_000_000));
+        assert_eq!(config.permitted_drift, None);
+
+        let meta = state.ledger_metadata.get();
+        assert_eq!(meta.icrc7_total_supply, 42);
+        assert_eq!(meta.txn_count, 60);
+        assert_eq!(meta.next_token_id, 43);
+        assert_eq!(meta.archive_log_canister, Some(archive(1)));
+        assert_eq!(meta.sync_pending_txn_ids, Some(vec![8, 9]));
+        assert_eq!(meta.archive_txn_count, 10);
+
+        assert_eq!(state.approval_ledger_info.get().max_approvals, 7);
+        let info = state.archive_ledger_info.get();
+        let archives = info.archives.clone().unwrap();
+        assert_eq!(archives.len(), 1);
+        assert_eq!(archives[&archive(1)].length, 10);
+        assert_eq!(info.first_index, 10);
+        assert_eq!(info.last_index, 59);
+
+        // The legacy state is cleared, a second run has nothing to move.
+        let mut len = [0; 4];
+        memory.read(0, &mut len);
+        assert_eq!(u32::from_le_bytes(len), 0);
+    }
+
+    #[test]
+    fn ledger_lists_move_out_of_the_cells() {
+        let mut state = State::default();
+        state.update_archive_ledger_info(|info| {
+            info.archives = Some(BTreeMap::from([
+                (archive(1), range(0, 10)),
+                (archive(2), range(10, 4)),
+            ]))
+        });
+        state.update_ledger_metadata(|meta| meta.sync_pending_txn_ids = Some(vec![14, 15]));
+
+        assert!(matches!(
+            move_ledger_lists(&mut state, None),
+            MigrationStep::Done
+        ));
+        assert!(state.archive_ledger_info.get().archives.is_none());
+        assert!(state.ledger_metadata.get().sync_pending_txn_ids.is_none());
+        assert_eq!(state.archives.len(), 2);
+        assert_eq!(state.archives.get(&archive(2)).unwrap().start, 10);
+        assert_eq!(state.get_sync_pending_txn_ids(), Some(vec![14, 15]));
+    }
+
+    #[test]
+    fn owner_index_migrates_in_batches() {
+        let mut state = State::default();
+        let tokens = 2 * MIGRATION_BATCH_SIZE as u128 + 500;
+        for token_id in 1..=tokens {
+            add_token(&mut state, token_id, account(1 + (token_id % 2) as u8));
+        }
+        set_version(&mut state, 1);
+
+        let mut cursor = None;
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            match index_token_owners(&mut state, cursor) {
+                MigrationStep::Continue(next) => cursor = Some(next),
+                MigrationStep::Done => break,
+            }
+        }
+        assert_eq!(steps, 3);
+        assert_eq!(state.owner_index.len() as u128, tokens);
+        assert_eq!(state.ledger_metadata.get().holders_count, Some(2));
+
+        set_version(&mut state, STATE_VERSION);
+        assert_eq!(
+            state.icrc7_balance_of(&[account(1), account(2), account(3)]),
+            vec![tokens / 2, tokens / 2, 0]
+        );
+    }
+
+    #[test]
+    fn owner_index_batches_resume_after_the_cursor() {
+        let mut state = State::default();
+        for token_id in 1..=4 {
+            add_token(&mut state, token_id, account(1));
+        }
+        assert_eq!(state.index_token_owners(None, 3), Some(3));
+        assert_eq!(state.owner_index.len(), 3);
+        assert_eq!(state.index_token_owners(Some(3), 3), None);
+        assert_eq!(state.owner_index.len(), 4);
+
+        // A batch that ends on the last token still reports it, and the next
+        // one is empty.
+        assert_eq!(state.index_token_owners(Some(2), 2), Some(4));
+        assert_eq!(state.index_token_owners(Some(4), 2), None);
+        assert_eq!(state.owner_index.len(), 4);
+    }
+
+    #[test]
+    fn holders_are_recounted_in_batches() {
+        let mut state = State::default();
+        for (token_id, owner) in [(1, 1), (2, 1), (3, 2), (4, 2), (5, 2), (6, 3)] {
+            add_token(&mut state, token_id, account(owner));
+        }
+        state.index_token_owners(None, 10);
+        state.update_ledger_metadata(|meta| meta.holders_count = None);
+
+        // Batches of two split the tokens of the second holder.
+        let mut prev = None;
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            prev = state.count_holders(prev, 2);
+            if prev.is_none() {
+                break;
+            }
+        }
+        assert_eq!(steps, 4);
+        assert_eq!(state.ledger_metadata.get().holders_count, Some(3));
+
+        // A recount starts over instead of adding to a stale counter.
+        state.update_ledger_metadata(|meta| meta.holders_count = Some(99));
+        assert!(matches!(
+            count_holders(&mut state, None),
+            MigrationStep::Done
+        ));
+        assert_eq!(state.ledger_metadata.get().holders_count, Some(3));
+    }
+
+    #[test]
+    fn dedup_index_seeds_recent_transfers_in_batches() {
+        let mut state = State::default();
+        let now = 2 * State::DEFAULT_TX_WINDOW;
+        let txns = [
+            Transaction::mint(now, 1, None, account(1), BTreeMap::new(), None),
+            Transaction::transfer(0, 1, account(1), account(2), None),
+            Transaction::transfer(now, 1, account(1), account(2), None),
+            Transaction::transfer_from(now, 1, account(2), account(3), account(4), None),
+            Transaction::transfer(now, 1, account(3), account(1), Some(vec![1])),
+        ];
+        for (txn_id, txn) in txns.iter().enumerate() {
+            state.txn_ledger.insert(txn_id as u128, txn.clone());
+        }
+
+        assert_eq!(state.seed_dedup_index(None, 2, now), Some(1));
+        assert_eq!(state.seed_dedup_index(Some(1), 2, now), Some(3));
+        assert_eq!(state.seed_dedup_index(Some(3), 2, now), None);
+
+        // The mint and the transfer outside of the window are skipped.
+        assert_eq!(state.dedup_index.len(), 3);
+        assert_eq!(state.dedup_expiry.len(), 3);
+        assert_eq!(
+            state.dedup_index.get(&transfer_hash(&txns[2], account(1))),
+            Some(2)
+        );
+        assert_eq!(
+            state.dedup_index.get(&transfer_hash(&txns[3], account(4))),
+            Some(3)
+        );
+        assert_eq!(
+            state.dedup_index.get(&transfer_hash(&txns[4], account(3))),
+            Some(4)
+        );
+        assert_eq!(
+            state.dedup_index.get(&transfer_hash(&txns[1], account(1))),
+            None
+        );
+    }
+
+    #[test]
+    fn failed_steps_show_in_the_status() {
+        let mut state = State::default();
+        set_version(&mut state, 1);
+        assert_eq!(record_migration_failure(&mut state, "first".into()), 1);
+        assert_eq!(record_migration_failure(&mut state, "second".into()), 2);
+
+        let status = migration_status(&state);
+        assert_eq!(status.failed_attempts, 2);
+        assert_eq!(status.last_error.as_deref(), Some("second"));
+        assert_eq!(status.version, 1);
+        assert!(status.pending.is_some());
+    }
+}
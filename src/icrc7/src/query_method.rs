@@ -2,7 +2,11 @@ use candid::Principal;
 use ic_cdk_macros::query;
 use icrc_ledger_types::icrc1::account::Account;
 
-use crate::{icrc7_types::Transaction, state::STATE, Icrc7TokenMetadata, Standard};
+use crate::{
+    icrc7_types::{MigrationStatus, Transaction},
+    state::STATE,
+    Icrc7TokenMetadata, Standard,
+};
 
 #[query]
 pub fn icrc7_symbol() -> String {
@@ -111,6 +115,11 @@ pub fn icrc7_tokens_of(account: Account, prev: Option<u128>, take: Option<u128>)
     STATE.with(|s| s.borrow().icrc7_tokens_of(account, prev, take))
 }
 
+#[query]
+pub fn holders_count() -> u64 {
+    STATE.with(|s| s.borrow().holders_count())
+}
+
 #[query]
 pub fn minting_authority() -> Option<Account> {
     STATE.with(|s| s.borrow().icrc7_minting_authority())
@@ -125,3 +134,8 @@ pub fn txn_logs(page_number: u32, page_size: u32) -> Vec<Transaction> {
 pub fn archive_log_canister() -> Option<Principal> {
     STATE.with(|s| s.borrow().get_archive_log_canister())
 }
+
+#[query]
+pub fn migration_status() -> MigrationStatus {
+    STATE.with(|s| crate::migration::migration_status(&s.borrow()))
+}
@@ -1,10 +1,16 @@
-use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    ops::Bound::{Excluded, Included, Unbounded},
+    time::Duration,
+};
 
 use crate::{
     archive::create_archive_canister,
     errors::{
         ApproveCollectionError, ApproveTokenError, BurnError, InsertTransactionError, MintError,
         RevokeCollectionApprovalError, RevokeTokenApprovalError, TransferError, TransferFromError,
+        TxnDedupError,
     },
     icrc37_types::{
         ApproveCollectionArg, ApproveCollectionResult, ApproveTokenArg, ApproveTokenResult,
@@ -23,17 +29,24 @@ use crate::{
         TransferArg, TransferResult,
     },
     memory::{
-        get_collection_approvals_memory, get_log_memory, get_token_approvals_memory,
+        get_approval_ledger_info_memory, get_archive_ledger_info_memory, get_archives_memory,
+        get_collection_approvals_memory, get_config_memory, get_dedup_expiry_memory,
+        get_dedup_index_memory, get_ledger_metadata_memory, get_log_memory, get_migration_memory,
+        get_owner_index_memory, get_sync_pending_txn_ids_memory, get_token_approvals_memory,
         get_token_map_memory, Memory,
     },
-    utils::{account_transformer, burn_account, hash_icrc_value},
-    BurnArg, SyncReceipt, TRANSACTION_TRANSFER_FROM_OP, TRANSACTION_TRANSFER_OP,
+    migration::{MigrationState, STATE_VERSION},
+    utils::{account_transformer, burn_account, hash_icrc_value, txn_dedup_hash},
+    BurnArg, SyncReceipt, TRANSACTION_APPROVE_COLLECTION_OP, TRANSACTION_APPROVE_OP,
+    TRANSACTION_BURN_OP, TRANSACTION_MINT_OP, TRANSACTION_REVOKE_COLLECTION_OP,
+    TRANSACTION_REVOKE_OP, TRANSACTION_TRANSFER_FROM_OP, TRANSACTION_TRANSFER_OP,
 };
 use candid::{CandidType, Decode, Encode, Principal};
 use ic_cdk_timers::TimerId;
 use ic_certified_map::{leaf_hash, AsHashTree, Hash, RbTree};
 use ic_stable_structures::{
-    memory_manager::MemoryManager, storable::Bound, DefaultMemoryImpl, StableBTreeMap, Storable,
+    memory_manager::MemoryManager, storable::Bound, DefaultMemoryImpl, StableBTreeMap, StableCell,
+    Storable,
 };
 use icrc_ledger_types::{
     icrc::generic_value::Value, icrc1::account::Account, icrc3::blocks::DataCertificate,
@@ -63,6 +76,25 @@ impl Storable for Icrc7Token {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+/// Key of the owner index, which orders tokens by owner and then by id.
+#[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OwnerTokenKey {
+    pub owner: Account,
+    pub token_id: u128,
+}
+
+impl Storable for OwnerTokenKey {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 impl Icrc7Token {
     fn new(
         token_id: u128,
@@ -104,14 +136,14 @@ impl Icrc7Token {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct State {
+/// Collection settings, set at init.
+#[derive(CandidType, Serialize, Deserialize, Clone)]
+pub struct CollectionConfig {
     pub minting_authority: Option<Account>,
     pub icrc7_symbol: String,
     pub icrc7_name: String,
     pub icrc7_description: Option<String>,
     pub icrc7_logo: Option<String>,
-    pub icrc7_total_supply: u128,
     pub icrc7_supply_cap: Option<u128>,
     pub icrc7_max_query_batch_size: Option<u16>,
     pub icrc7_max_update_batch_size: Option<u16>,
@@ -121,26 +153,9 @@ pub struct State {
     pub icrc7_atomic_batch_transfers: Option<bool>,
     pub tx_window: Option<u64>,
     pub permitted_drift: Option<u64>,
-    #[serde(skip, default = "get_token_map_memory")]
-    pub tokens: StableBTreeMap<u128, Icrc7Token, Memory>,
-    pub txn_count: u128,
-    pub next_token_id: u128,
-
-    pub approval_ledger_info: LedgerInfo,
-    #[serde(skip, default = "get_token_approvals_memory")]
-    pub token_approvals: StableBTreeMap<u128, TokenApprovalInfo, Memory>,
-    #[serde(skip, default = "get_collection_approvals_memory")]
-    pub collection_approvals: StableBTreeMap<UserAccount, CollectionApprovalInfo, Memory>,
-
-    pub archive_ledger_info: ArchiveLedgerInfo,
-    #[serde(skip, default = "get_log_memory")]
-    pub txn_ledger: StableBTreeMap<u128, Transaction, Memory>,
-    pub archive_log_canister: Option<Principal>,
-    pub sync_pending_txn_ids: Option<Vec<u128>>,
-    pub archive_txn_count: u128,
 }
 
-impl Default for State {
+impl Default for CollectionConfig {
     fn default() -> Self {
         Self {
             minting_authority: None,
@@ -148,7 +163,6 @@ impl Default for State {
             icrc7_name: "ICRC7 Collection".into(),
             icrc7_description: None,
             icrc7_logo: None,
-            icrc7_total_supply: 0,
             icrc7_supply_cap: None,
             icrc7_max_query_batch_size: None,
             icrc7_max_update_batch_size: None,
@@ -158,21 +172,114 @@ impl Default for State {
             icrc7_atomic_batch_transfers: None,
             tx_window: None,
             permitted_drift: None,
-            tokens: get_token_map_memory(),
+        }
+    }
+}
+
+impl Storable for CollectionConfig {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Counters and bookkeeping of the token and transaction ledgers.
+#[derive(CandidType, Serialize, Deserialize, Clone)]
+pub struct LedgerMetadata {
+    pub icrc7_total_supply: u128,
+    pub txn_count: u128,
+    pub next_token_id: u128,
+    pub archive_log_canister: Option<Principal>,
+    // Ids kept here before they moved to their own stable map. Only read by
+    // the migration that moves them there, `None` afterwards.
+    pub sync_pending_txn_ids: Option<Vec<u128>>,
+    pub archive_txn_count: u128,
+    // Accounts holding at least one token, kept by `index_token_owner` and
+    // seeded by the migrations over the owner index.
+    pub holders_count: Option<u64>,
+}
+
+impl Default for LedgerMetadata {
+    fn default() -> Self {
+        Self {
+            icrc7_total_supply: 0,
             txn_count: 0,
             next_token_id: 0,
-            txn_ledger: get_log_memory(),
             archive_log_canister: None,
             sync_pending_txn_ids: None,
             archive_txn_count: 0,
-            approval_ledger_info: LedgerInfo::default(),
+            holders_count: None,
+        }
+    }
+}
+
+impl Storable for LedgerMetadata {
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Everything lives in stable memory, so upgrades don't serialize the state.
+pub struct State {
+    pub migration: StableCell<MigrationState, Memory>,
+    pub config: StableCell<CollectionConfig, Memory>,
+    pub ledger_metadata: StableCell<LedgerMetadata, Memory>,
+    pub tokens: StableBTreeMap<u128, Icrc7Token, Memory>,
+    pub owner_index: StableBTreeMap<OwnerTokenKey, (), Memory>,
+    // Transactions with a `created_at_time`, by dedup hash, and the same
+    // hashes ordered by `created_at_time` so the expiry timer can drop them.
+    pub dedup_index: StableBTreeMap<[u8; 32], u128, Memory>,
+    pub dedup_expiry: StableBTreeMap<(u64, [u8; 32]), (), Memory>,
+
+    pub approval_ledger_info: StableCell<LedgerInfo, Memory>,
+    pub token_approvals: StableBTreeMap<u128, TokenApprovalInfo, Memory>,
+    pub collection_approvals: StableBTreeMap<UserAccount, CollectionApprovalInfo, Memory>,
+
+    pub archive_ledger_info: StableCell<ArchiveLedgerInfo, Memory>,
+    pub archives: StableBTreeMap<Principal, TransactionRange, Memory>,
+    pub txn_ledger: StableBTreeMap<u128, Transaction, Memory>,
+    pub sync_pending_txn_ids: StableBTreeMap<u128, (), Memory>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            migration: get_migration_memory(),
+            config: get_config_memory(),
+            ledger_metadata: get_ledger_metadata_memory(),
+            tokens: get_token_map_memory(),
+            owner_index: get_owner_index_memory(),
+            dedup_index: get_dedup_index_memory(),
+            dedup_expiry: get_dedup_expiry_memory(),
+            approval_ledger_info: get_approval_ledger_info_memory(),
             token_approvals: get_token_approvals_memory(),
             collection_approvals: get_collection_approvals_memory(),
-            archive_ledger_info: ArchiveLedgerInfo::default(),
+            archive_ledger_info: get_archive_ledger_info_memory(),
+            archives: get_archives_memory(),
+            txn_ledger: get_log_memory(),
+            sync_pending_txn_ids: get_sync_pending_txn_ids_memory(),
         }
     }
 }
 
+// Writes `f` applied to the current value back to `cell`.
+fn update_cell<T: Storable + Clone>(cell: &mut StableCell<T, Memory>, f: impl FnOnce(&mut T)) {
+    let mut value = cell.get().clone();
+    f(&mut value);
+    cell.set(value).expect("failed to write stable cell");
+}
+
 impl State {
     pub const DEFAULT_MAX_QUERY_BATCH_SIZE: u16 = 32;
     pub const DEFAULT_MAX_UPDATE_BATCH_SIZE: u16 = 32;
@@ -181,57 +288,58 @@ impl State {
     pub const DEFAULT_MAX_MEMO_SIZE: u32 = 32;
     pub const DEFAULT_TX_WINDOW: u64 = 24 * 60 * 60 * 1000_000_000;
     pub const DEFAULT_PERMITTED_DRIFT: u64 = 2 * 60 * 1000_000_000;
+    pub const DEDUP_EXPIRY_BATCH_SIZE: usize = 5_000;
 
     pub fn icrc7_symbol(&self) -> String {
-        self.icrc7_symbol.clone()
+        self.config.get().icrc7_symbol.clone()
     }
 
     pub fn icrc7_name(&self) -> String {
-        self.icrc7_name.clone()
+        self.config.get().icrc7_name.clone()
     }
 
     pub fn icrc7_description(&self) -> Option<String> {
-        self.icrc7_description.clone()
+        self.config.get().icrc7_description.clone()
     }
 
     pub fn icrc7_total_supply(&self) -> u128 {
-        self.icrc7_total_supply
+        self.ledger_metadata.get().icrc7_total_supply
     }
 
     pub fn icrc7_supply_cap(&self) -> Option<u128> {
-        self.icrc7_supply_cap
+        self.config.get().icrc7_supply_cap
     }
 
     pub fn icrc7_logo(&self) -> Option<String> {
-        self.icrc7_logo.clone()
+        self.config.get().icrc7_logo.clone()
     }
 
     pub fn icrc7_minting_authority(&self) -> Option<Account> {
-        self.minting_authority.clone()
+        self.config.get().minting_authority.clone()
     }
 
     pub fn icrc7_max_query_batch_size(&self) -> Option<u16> {
-        self.icrc7_max_query_batch_size
+        self.config.get().icrc7_max_query_batch_size
     }
 
     pub fn icrc7_max_update_batch_size(&self) -> Option<u16> {
-        self.icrc7_max_update_batch_size
+        self.config.get().icrc7_max_update_batch_size
     }
 
     pub fn icrc7_default_take_value(&self) -> Option<u128> {
-        self.icrc7_default_take_value
+        self.config.get().icrc7_default_take_value
     }
 
     pub fn icrc7_max_take_value(&self) -> Option<u128> {
-        self.icrc7_max_take_value
+        self.config.get().icrc7_max_take_value
     }
 
     pub fn icrc7_max_memo_size(&self) -> Option<u32> {
-        self.icrc7_max_memo_size
+        self.config.get().icrc7_max_memo_size
     }
 
     pub fn icrc7_atomic_batch_transfers(&self) -> Option<bool> {
-        self.icrc7_atomic_batch_transfers
+        self.config.get().icrc7_atomic_batch_transfers
     }
 
     pub fn icrc7_owner_of(&self, token_id: &[u128]) -> Vec<Option<Account>> {
@@ -248,6 +356,7 @@ impl State {
         let mut res = Metadata::new();
         if self
             .approval_ledger_info
+            .get()
             .max_approvals_per_token_or_collection
             > 0
         {
@@ -256,76 +365,179 @@ impl State {
                 Value::Nat(
                     (self
                         .approval_ledger_info
+                        .get()
                         .max_approvals_per_token_or_collection as u64)
                         .into(),
                 ),
             );
         }
-        if self.approval_ledger_info.max_revoke_approvals > 0 {
+        if self.approval_ledger_info.get().max_revoke_approvals > 0 {
             res.insert(
                 "icrc37:max_revoke_approvals".to_string(),
-                Value::Nat((self.approval_ledger_info.max_revoke_approvals as u64).into()),
+                Value::Nat((self.approval_ledger_info.get().max_revoke_approvals as u64).into()),
             );
         }
         res
     }
 
     pub fn get_archive_log_canister(&self) -> Option<Principal> {
-        self.archive_log_canister
+        self.ledger_metadata.get().archive_log_canister
     }
 
     pub fn get_sync_pending_txn_ids(&self) -> Option<Vec<u128>> {
-        self.sync_pending_txn_ids.clone()
+        if self.sync_pending_txn_ids.is_empty() {
+            return None;
+        }
+        Some(
+            self.sync_pending_txn_ids
+                .iter()
+                .map(|(txn_id, _)| txn_id)
+                .collect(),
+        )
     }
 
     pub fn set_sync_pending_txn_ids(&mut self, txn_ids: Option<Vec<u128>>) -> bool {
-        self.sync_pending_txn_ids = txn_ids;
+        while self.sync_pending_txn_ids.pop_first().is_some() {}
+        for txn_id in txn_ids.unwrap_or_default() {
+            self.sync_pending_txn_ids.insert(txn_id, ());
+        }
         return true;
     }
 
+    pub fn set_minting_authority(&mut self, minting_authority: Account) {
+        update_cell(&mut self.config, |config| {
+            config.minting_authority = Some(minting_authority)
+        });
+    }
+
+    pub fn update_ledger_metadata(&mut self, f: impl FnOnce(&mut LedgerMetadata)) {
+        update_cell(&mut self.ledger_metadata, f);
+    }
+
+    pub fn update_archive_ledger_info(&mut self, f: impl FnOnce(&mut ArchiveLedgerInfo)) {
+        update_cell(&mut self.archive_ledger_info, f);
+    }
+
+    pub fn set_archive_cleaning(&mut self, is_cleaning: bool) {
+        self.update_archive_ledger_info(|info| info.is_cleaning = is_cleaning);
+    }
+
+    fn allowed_past_time(&self, current_time: u64) -> u64 {
+        current_time
+            .saturating_sub(
+                self.config
+                    .get()
+                    .tx_window
+                    .unwrap_or(State::DEFAULT_TX_WINDOW),
+            )
+            .saturating_sub(
+                self.config
+                    .get()
+                    .permitted_drift
+                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT),
+            )
+    }
+
+    // Rejects a `created_at_time` outside of the transaction window, and a
+    // transaction whose dedup hash is already in the index.
     fn txn_deduplication_check(
         &self,
-        allowed_past_time: &u64,
-        caller: &Account,
-        args: &TransferArg,
-    ) -> Result<(), TransferError> {
-        let mut count = self.txn_count;
-        while count != 0 {
-            if self.txn_ledger.get(&count).is_none() {
-                count -= 1;
+        hash: &[u8; 32],
+        created_at_time: u64,
+        current_time: u64,
+    ) -> Result<(), TxnDedupError> {
+        if !self.is_migrated() {
+            return Err(TxnDedupError::Migrating);
+        }
+        let allowed_future_time = current_time
+            + self
+                .config
+                .get()
+                .permitted_drift
+                .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
+        if created_at_time < self.allowed_past_time(current_time) {
+            return Err(TxnDedupError::TooOld);
+        } else if created_at_time > allowed_future_time {
+            return Err(TxnDedupError::CreatedInFuture {
+                ledger_time: current_time,
+            });
+        }
+        match self.dedup_index.get(hash) {
+            Some(duplicate_of) => Err(TxnDedupError::Duplicate { duplicate_of }),
+            None => Ok(()),
+        }
+    }
+
+    fn record_txn_dedup(&mut self, hash: [u8; 32], created_at_time: u64, txn_id: u128) {
+        self.dedup_index.insert(hash, txn_id);
+        self.dedup_expiry.insert((created_at_time, hash), ());
+    }
+
+    // Drops dedup entries that fell out of the transaction window, at most
+    // `DEDUP_EXPIRY_BATCH_SIZE` per call.
+    pub fn expire_txn_dedup(&mut self, current_time: u64) {
+        let allowed_past_time = self.allowed_past_time(current_time);
+        let expired: Vec<(u64, [u8; 32])> = self
+            .dedup_expiry
+            .range(..(allowed_past_time, [0; 32]))
+            .take(State::DEDUP_EXPIRY_BATCH_SIZE)
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            self.dedup_index.remove(&key.1);
+            self.dedup_expiry.remove(&key);
+        }
+    }
+
+    // Seeds the dedup index from the transfers in the local ledger, which is
+    // what collections without the index deduplicated against. Handles up to
+    // `limit` transactions after `prev`, and returns the last transaction id
+    // handled if transactions remain.
+    pub fn seed_dedup_index(
+        &mut self,
+        prev: Option<u128>,
+        limit: usize,
+        current_time: u64,
+    ) -> Option<u128> {
+        let allowed_past_time = self.allowed_past_time(current_time);
+        let start = match prev {
+            Some(prev) => Excluded(prev),
+            None => Unbounded,
+        };
+        let batch: Vec<(u128, Transaction)> = self
+            .txn_ledger
+            .range((start, Unbounded))
+            .take(limit)
+            .collect();
+        let last = batch.last().map(|(txn_id, _)| *txn_id);
+        let is_last_batch = batch.len() < limit;
+        for (txn_id, txn) in batch {
+            if txn.ts < allowed_past_time {
                 continue;
             }
-            let txn = self.txn_ledger.get(&count).unwrap();
-            if txn.ts < *allowed_past_time {
-                return Ok(());
-            }
-            if txn.op == String::from(TRANSACTION_TRANSFER_OP)
-                || txn.op == String::from(TRANSACTION_TRANSFER_FROM_OP)
-            {
-                if args.token_id == txn.tid
-                    && caller == txn.from.as_ref().unwrap()
-                    && args.to == txn.to.unwrap()
-                    && args.memo == txn.memo
-                    && args.created_at_time == Some(txn.ts)
-                {
-                    return Err(TransferError::Duplicate {
-                        duplicate_of: count,
-                    });
-                } else {
-                    count -= 1;
-                    continue;
-                }
+            let caller = if txn.op == TRANSACTION_TRANSFER_OP {
+                txn.from
+            } else if txn.op == TRANSACTION_TRANSFER_FROM_OP {
+                txn.spender
             } else {
-                count -= 1;
                 continue;
+            };
+            if let (Some(caller), Some(to)) = (caller, txn.to) {
+                let hash =
+                    txn_dedup_hash(&txn.op, &caller, Some(txn.tid), Some(to), &txn.memo, txn.ts);
+                self.record_txn_dedup(hash, txn.ts, txn_id);
             }
         }
-        Ok(())
+        if is_last_batch {
+            None
+        } else {
+            last
+        }
     }
 
     fn get_txn_id(&mut self) -> u128 {
-        let tx_id = self.txn_count;
-        self.txn_count += 1;
+        let tx_id = self.ledger_metadata.get().txn_count;
+        self.update_ledger_metadata(|meta| meta.txn_count += 1);
         tx_id
     }
 
@@ -338,7 +550,7 @@ impl State {
         let txn_id = self.get_txn_id();
 
         // Get the information of the previous transaction.
-        // let current_size = self.archive_ledger_info.local_ledger_size;
+        // let current_size = self.archive_ledger_info.get().local_ledger_size;
         // let last_transaction: Option<Transaction> = if current_size == 0 {
         //     None
         // } else {
@@ -346,30 +558,32 @@ impl State {
         // };
 
         let mut txn = Transaction::new(txn_id, txn_type, at, memo);
-        let phash = self.archive_ledger_info.latest_hash;
+        let phash = self.archive_ledger_info.get().latest_hash;
 
         let block = Block::new(phash, txn.clone());
         let block_hash = hash_icrc_value(block.as_ref());
 
         txn.block = Some(block);
         self.txn_ledger.insert(txn_id, txn);
-        self.archive_ledger_info.last_index += 1;
-        self.archive_ledger_info.latest_hash = Some(block_hash);
-        self.archive_ledger_info.local_ledger_size += 1;
+        self.update_archive_ledger_info(|info| {
+            info.last_index += 1;
+            info.latest_hash = Some(block_hash);
+            info.local_ledger_size += 1;
+        });
 
         // set certified data
         TREE.with(|tree| {
             let mut tree = tree.borrow_mut();
             tree.insert(
                 "last_block_index",
-                leaf_hash(&self.archive_ledger_info.last_index.to_be_bytes()),
+                leaf_hash(&self.archive_ledger_info.get().last_index.to_be_bytes()),
             );
             tree.insert("last_block_hash", leaf_hash(&block_hash));
             ic_cdk::api::set_certified_data(&tree.root_hash());
         });
 
-        if self.archive_ledger_info.local_ledger_size
-            > self.archive_ledger_info.setting.max_active_records
+        if self.archive_ledger_info.get().local_ledger_size
+            > self.archive_ledger_info.get().setting.max_active_records
         {
             set_clean_up_timer();
         }
@@ -378,11 +592,13 @@ impl State {
     }
 
     fn get_current_txn_count(&self) -> u128 {
-        self.txn_count - self.archive_txn_count
+        self.ledger_metadata.get().txn_count - self.ledger_metadata.get().archive_txn_count
     }
 
     fn get_current_take(&self, take: Option<u128>) -> u128 {
-        self.icrc7_max_take_value
+        self.config
+            .get()
+            .icrc7_max_take_value
             .map_or(self::State::DEFAULT_TAKE_VALUE, |max_take| {
                 take.map_or(max_take, |t| t.min(max_take))
             })
@@ -440,23 +656,15 @@ impl State {
         arg: &TransferArg,
     ) -> Result<(), TransferError> {
         if let Some(time) = arg.created_at_time {
-            let allowed_past_time = *current_time
-                - self.tx_window.unwrap_or(State::DEFAULT_TX_WINDOW)
-                - self
-                    .permitted_drift
-                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
-            let allowed_future_time = *current_time
-                + self
-                    .permitted_drift
-                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
-            if time < allowed_past_time {
-                return Err(TransferError::TooOld);
-            } else if time > allowed_future_time {
-                return Err(TransferError::CreatedInFuture {
-                    ledger_time: current_time.clone(),
-                });
-            }
-            self.txn_deduplication_check(&allowed_past_time, caller, arg)?;
+            let hash = txn_dedup_hash(
+                TRANSACTION_TRANSFER_OP,
+                caller,
+                Some(arg.token_id),
+                Some(arg.to),
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
         }
         // checking is token for the corresponding ID exists or not
         if let None = self.tokens.get(&arg.token_id) {
@@ -464,6 +672,8 @@ impl State {
         }
         if let Some(ref memo) = arg.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -498,6 +708,8 @@ impl State {
             }))];
         }
         let max_update_batch_size = self
+            .config
+            .get()
             .icrc7_max_query_batch_size
             .unwrap_or(State::DEFAULT_MAX_UPDATE_BATCH_SIZE);
         let mut txn_results = vec![None; args.len()];
@@ -526,7 +738,7 @@ impl State {
                 txn_results[index] = Some(Err(e));
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -550,6 +762,7 @@ impl State {
                 }
             }
             let mut token = self.tokens.get(&arg.token_id).unwrap();
+            self.index_token_owner(arg.token_id, Some(token.token_owner), Some(arg.to));
             token.transfer(arg.to.clone());
             self.tokens.insert(arg.token_id, token);
             let txn_id = self.log_transaction(
@@ -561,28 +774,57 @@ impl State {
                 time,
                 arg.memo.clone(),
             );
+            if let Some(created_at_time) = arg.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_TRANSFER_OP,
+                    &caller_account,
+                    Some(arg.token_id),
+                    Some(arg.to),
+                    &arg.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, txn_id);
+            }
             txn_results[index] = Some(Ok(txn_id));
         }
         txn_results
     }
 
-    fn mock_mint(&self, caller: &Account, arg: &MintArg) -> Result<(), MintError> {
-        if let Some(cap) = self.icrc7_supply_cap {
-            if cap == self.icrc7_total_supply {
+    fn mock_mint(
+        &self,
+        current_time: &u64,
+        caller: &Account,
+        arg: &MintArg,
+    ) -> Result<(), MintError> {
+        if let Some(time) = arg.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_MINT_OP,
+                caller,
+                Some(arg.token_id),
+                Some(arg.to),
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
+        if let Some(cap) = self.config.get().icrc7_supply_cap {
+            if cap == self.ledger_metadata.get().icrc7_total_supply {
                 return Err(MintError::SupplyCapReached);
             }
         }
-        if let None = self.minting_authority {
+        if let None = self.config.get().minting_authority {
             return Err(MintError::GenericBatchError {
                 error_code: 6,
                 message: "Minting Authority Not Set".into(),
             });
         }
-        if Some(*caller) != self.minting_authority {
+        if Some(*caller) != self.config.get().minting_authority {
             return Err(MintError::Unauthorized);
         }
         if let Some(ref memo) = arg.memo {
             let allowed_memo_length = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > allowed_memo_length {
@@ -593,7 +835,7 @@ impl State {
             }
         }
         // this is weird, and not needed
-        // if &arg.token_id < &self.next_token_id {
+        // if &arg.token_id < &self.ledger_metadata.get().next_token_id {
         //     return Err(MintError::TokenIdMinimumLimit);
         // }
         if let Some(_) = self.tokens.get(&arg.token_id) {
@@ -608,9 +850,10 @@ impl State {
             subaccount: arg.from_subaccount,
         });
         arg.to = account_transformer(arg.to);
-        self.mock_mint(&caller, &arg)?;
+        let current_time = ic_cdk::api::time();
+        self.mock_mint(&current_time, &caller, &arg)?;
         let token_name = arg.token_name.unwrap_or_else(|| {
-            let name = format!("{} {}", self.icrc7_symbol, arg.token_id);
+            let name = format!("{} {}", self.config.get().icrc7_symbol, arg.token_id);
             name
         });
         let token = Icrc7Token::new(
@@ -623,7 +866,8 @@ impl State {
         );
         let token_metadata = token.token_metadata();
         self.tokens.insert(arg.token_id, token);
-        self.icrc7_total_supply += 1;
+        self.index_token_owner(arg.token_id, None, Some(arg.to));
+        self.update_ledger_metadata(|meta| meta.icrc7_total_supply += 1);
         // self.next_token_id = arg.token_id + 1;
 
         let txn_id = self.log_transaction(
@@ -633,16 +877,45 @@ impl State {
                 to: arg.to,
                 meta: token_metadata,
             },
-            ic_cdk::api::time(),
-            arg.memo,
+            current_time,
+            arg.memo.clone(),
         );
+        if let Some(created_at_time) = arg.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_MINT_OP,
+                &caller,
+                Some(arg.token_id),
+                Some(arg.to),
+                &arg.memo,
+                created_at_time,
+            );
+            self.record_txn_dedup(hash, created_at_time, txn_id);
+        }
         Ok(txn_id)
     }
 
-    fn mock_burn(&self, caller: &Account, arg: &BurnArg) -> Result<(), BurnError> {
+    fn mock_burn(
+        &self,
+        current_time: &u64,
+        caller: &Account,
+        arg: &BurnArg,
+    ) -> Result<(), BurnError> {
+        if let Some(time) = arg.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_BURN_OP,
+                caller,
+                Some(arg.token_id),
+                None,
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
         if let Some(ref memo) = arg.memo {
             if memo.len() as u32
                 > self
+                    .config
+                    .get()
                     .icrc7_max_memo_size
                     .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE)
             {
@@ -678,16 +951,17 @@ impl State {
             }));
             return txn_results;
         }
+        let current_time = ic_cdk::api::time();
         for (index, arg) in args.iter_mut().enumerate() {
             let caller = account_transformer(Account {
                 owner: caller.clone(),
                 subaccount: arg.from_subaccount,
             });
-            if let Err(e) = self.mock_burn(&caller, arg) {
+            if let Err(e) = self.mock_burn(&current_time, &caller, arg) {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -718,8 +992,10 @@ impl State {
             // token.burn(burn_address.clone());
             // self.tokens.insert(arg.token_id, token);
             // should be properly burn
-            self.tokens.remove(&arg.token_id);
-            self.icrc7_total_supply -= 1;
+            if let Some(token) = self.tokens.remove(&arg.token_id) {
+                self.index_token_owner(arg.token_id, Some(token.token_owner), None);
+            }
+            self.update_ledger_metadata(|meta| meta.icrc7_total_supply -= 1);
 
             let tid = self.log_transaction(
                 TransactionType::Burn {
@@ -727,9 +1003,20 @@ impl State {
                     from: caller,
                     to: burn_address,
                 },
-                ic_cdk::api::time(),
+                current_time,
                 arg.memo.clone(),
             );
+            if let Some(created_at_time) = arg.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_BURN_OP,
+                    &caller,
+                    Some(arg.token_id),
+                    None,
+                    &arg.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, tid);
+            }
             txn_results.insert(index, Some(Ok(tid)))
         }
         txn_results
@@ -739,12 +1026,26 @@ impl State {
         &self,
         caller: &Account,
         arg: &ApproveTokenArg,
+        current_time: &u64,
     ) -> Result<(), ApproveTokenError> {
         if arg.approval_info.spender == *caller {
             return Err(ApproveTokenError::InvalidSpender);
         };
+        if let Some(time) = arg.approval_info.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_APPROVE_OP,
+                caller,
+                Some(arg.token_id),
+                Some(arg.approval_info.spender),
+                &arg.approval_info.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
         if let Some(ref memo) = arg.approval_info.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -787,17 +1088,18 @@ impl State {
         }
 
         let mut txn_results = vec![None; args.len()];
+        let current_time = ic_cdk::api::time();
 
         for (index, arg) in args.iter_mut().enumerate() {
             let caller = account_transformer(Account {
                 owner: caller.clone(),
                 subaccount: arg.approval_info.from_subaccount,
             });
-            if let Err(e) = self.mock_approve(&caller, arg) {
+            if let Err(e) = self.mock_approve(&caller, arg, &current_time) {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -841,6 +1143,17 @@ impl State {
                 ic_cdk::api::time(),
                 arg.approval_info.memo.clone(),
             );
+            if let Some(created_at_time) = arg.approval_info.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_APPROVE_OP,
+                    &caller,
+                    Some(arg.token_id),
+                    Some(arg.approval_info.spender),
+                    &arg.approval_info.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, tid);
+            }
             txn_results.insert(index, Some(Ok(tid)))
         }
         txn_results
@@ -860,9 +1173,22 @@ impl State {
                 return Err(ApproveCollectionError::TooOld);
             }
         }
+        if let Some(time) = arg.approval_info.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_APPROVE_COLLECTION_OP,
+                caller,
+                None,
+                Some(arg.approval_info.spender),
+                &arg.approval_info.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
 
         if let Some(ref memo) = arg.approval_info.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -908,7 +1234,7 @@ impl State {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -957,6 +1283,17 @@ impl State {
                 ic_cdk::api::time(),
                 arg.approval_info.memo.clone(),
             );
+            if let Some(created_at_time) = arg.approval_info.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_APPROVE_COLLECTION_OP,
+                    &caller,
+                    None,
+                    Some(arg.approval_info.spender),
+                    &arg.approval_info.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, tid);
+            }
             txn_results.insert(index, Some(Ok(tid)))
         }
 
@@ -967,6 +1304,7 @@ impl State {
         &self,
         caller: &Account,
         arg: &RevokeTokenApprovalArg,
+        current_time: &u64,
     ) -> Result<(), RevokeTokenApprovalError> {
         if let Some(spender) = arg.spender {
             if spender == *caller {
@@ -976,9 +1314,22 @@ impl State {
                 });
             }
         }
+        if let Some(time) = arg.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_REVOKE_OP,
+                caller,
+                Some(arg.token_id),
+                arg.spender,
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
 
         if let Some(ref memo) = arg.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -1022,17 +1373,18 @@ impl State {
         }
 
         let mut txn_results: Vec<Option<RevokeTokenApprovalResult>> = vec![None; args.len()];
+        let current_time = ic_cdk::api::time();
 
         for (index, arg) in args.iter_mut().enumerate() {
             let caller = account_transformer(Account {
                 owner: caller.clone(),
                 subaccount: arg.from_subaccount,
             });
-            if let Err(e) = self.mock_revoke_approve(&caller, arg) {
+            if let Err(e) = self.mock_revoke_approve(&caller, arg, &current_time) {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -1074,6 +1426,17 @@ impl State {
                 ic_cdk::api::time(),
                 arg.memo.clone(),
             );
+            if let Some(created_at_time) = arg.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_REVOKE_OP,
+                    &caller,
+                    Some(arg.token_id),
+                    arg.spender,
+                    &arg.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, tid);
+            }
             txn_results.insert(index, Some(Ok(tid)))
         }
         return txn_results;
@@ -1093,19 +1456,22 @@ impl State {
                 });
             }
         }
-        if let Some(created_at_time) = arg.created_at_time {
-            let allowed_future_time = *current_time
-                + self
-                    .permitted_drift
-                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
-
-            if created_at_time < allowed_future_time {
-                return Err(RevokeCollectionApprovalError::TooOld);
-            }
+        if let Some(time) = arg.created_at_time {
+            let hash = txn_dedup_hash(
+                TRANSACTION_REVOKE_COLLECTION_OP,
+                caller,
+                None,
+                arg.spender,
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
         }
 
         if let Some(ref memo) = arg.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -1151,7 +1517,7 @@ impl State {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -1196,6 +1562,17 @@ impl State {
                 ic_cdk::api::time(),
                 arg.memo.clone(),
             );
+            if let Some(created_at_time) = arg.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_REVOKE_COLLECTION_OP,
+                    &caller,
+                    None,
+                    arg.spender,
+                    &arg.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, tid);
+            }
             txn_results.insert(index, Some(Ok(tid)))
         }
         return txn_results;
@@ -1216,49 +1593,27 @@ impl State {
         // }
 
         if let Some(time) = arg.created_at_time {
-            let allowed_past_time = *current_time
-                - self.tx_window.unwrap_or(State::DEFAULT_TX_WINDOW)
-                - self
-                    .permitted_drift
-                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
-            let allowed_future_time = *current_time
-                + self
-                    .permitted_drift
-                    .unwrap_or(State::DEFAULT_PERMITTED_DRIFT);
-            if time < allowed_past_time {
-                return Err(TransferFromError::TooOld);
-            } else if time > allowed_future_time {
-                return Err(TransferFromError::CreatedInFuture {
-                    ledger_time: current_time.clone(),
-                });
-            }
-
-            if !self.is_approved_by_collection(&arg.from, &caller, *current_time)
-                && !self.is_approved_by_token(&arg.token_id, &arg.from, &caller, *current_time)
-            {
-                return Err(TransferFromError::Unauthorized);
-            }
+            let hash = txn_dedup_hash(
+                TRANSACTION_TRANSFER_FROM_OP,
+                caller,
+                Some(arg.token_id),
+                Some(arg.to),
+                &arg.memo,
+                time,
+            );
+            self.txn_deduplication_check(&hash, time, *current_time)?;
+        }
 
-            let transfer_arg: TransferArg = arg.clone().into();
-            let result = self.txn_deduplication_check(&allowed_past_time, caller, &transfer_arg);
-            match result {
-                Ok(_) => (),
-                Err(_) => {
-                    return Err(TransferFromError::Duplicate {
-                        duplicate_of: (arg.token_id),
-                    });
-                }
-            }
-        } else {
-            if !self.is_approved_by_collection(&arg.from, &caller, *current_time)
-                && !self.is_approved_by_token(&arg.token_id, &arg.from, &caller, *current_time)
-            {
-                return Err(TransferFromError::Unauthorized);
-            }
+        if !self.is_approved_by_collection(&arg.from, &caller, *current_time)
+            && !self.is_approved_by_token(&arg.token_id, &arg.from, &caller, *current_time)
+        {
+            return Err(TransferFromError::Unauthorized);
         }
 
         if let Some(ref memo) = arg.memo {
             let max_memo_size = self
+                .config
+                .get()
                 .icrc7_max_memo_size
                 .unwrap_or(State::DEFAULT_MAX_MEMO_SIZE);
             if memo.len() as u32 > max_memo_size {
@@ -1304,7 +1659,7 @@ impl State {
                 txn_results.insert(index, Some(Err(e)))
             }
         }
-        if let Some(true) = self.icrc7_atomic_batch_transfers {
+        if let Some(true) = self.config.get().icrc7_atomic_batch_transfers {
             if txn_results
                 .iter()
                 .any(|res| res.is_some() && res.as_ref().unwrap().is_err())
@@ -1329,6 +1684,7 @@ impl State {
                 }
             }
             let mut token = self.tokens.get(&arg.token_id).unwrap();
+            self.index_token_owner(arg.token_id, Some(token.token_owner), Some(arg.to));
             token.transfer(arg.to.clone());
             self.token_approvals_clean(&arg.token_id);
             self.tokens.insert(arg.token_id, token);
@@ -1342,6 +1698,17 @@ impl State {
                 time,
                 arg.memo.clone(),
             );
+            if let Some(created_at_time) = arg.created_at_time {
+                let hash = txn_dedup_hash(
+                    TRANSACTION_TRANSFER_FROM_OP,
+                    &caller_account,
+                    Some(arg.token_id),
+                    Some(arg.to),
+                    &arg.memo,
+                    created_at_time,
+                );
+                self.record_txn_dedup(hash, created_at_time, txn_id);
+            }
             txn_results[index] = Some(Ok(txn_id));
         }
 
@@ -1462,6 +1829,8 @@ impl State {
     pub fn icrc7_token_metadata(&self, token_ids: &[u128]) -> Vec<Option<Icrc7TokenMetadata>> {
         if token_ids.len() as u16
             > self
+                .config
+                .get()
                 .icrc7_max_query_batch_size
                 .unwrap_or(State::DEFAULT_MAX_QUERY_BATCH_SIZE)
         {
@@ -1476,42 +1845,187 @@ impl State {
         metadata_list
     }
 
-    pub fn icrc7_balance_of(&self, accounts: &[Account]) -> Vec<u128> {
-        let mut count_list = vec![0; accounts.len()];
-        accounts.iter().enumerate().for_each(|(index, account)| {
-            self.tokens.iter().for_each(|(_id, ref token)| {
-                if token.token_owner == *account {
-                    let current_count = count_list[index];
-                    count_list[index] = current_count + 1;
-                }
-            })
+    // Moves `token_id` between owners in the owner index, `None` stands for
+    // a token that is minted or burned.
+    fn index_token_owner(&mut self, token_id: u128, from: Option<Account>, to: Option<Account>) {
+        if let Some(owner) = from {
+            if self
+                .owner_index
+                .remove(&OwnerTokenKey { owner, token_id })
+                .is_some()
+                && !self.holds_tokens(owner)
+            {
+                self.add_holders(-1);
+            }
+        }
+        if let Some(owner) = to {
+            self.add_owner_key(OwnerTokenKey { owner, token_id });
+        }
+    }
+
+    fn add_owner_key(&mut self, key: OwnerTokenKey) {
+        let new_holder = !self.holds_tokens(key.owner);
+        if self.owner_index.insert(key, ()).is_none() && new_holder {
+            self.add_holders(1);
+        }
+    }
+
+    fn holds_tokens(&self, owner: Account) -> bool {
+        self.owner_index
+            .range(
+                OwnerTokenKey { owner, token_id: 0 }..=OwnerTokenKey {
+                    owner,
+                    token_id: u128::MAX,
+                },
+            )
+            .next()
+            .is_some()
+    }
+
+    fn add_holders(&mut self, delta: i64) {
+        self.update_ledger_metadata(|meta| {
+            meta.holders_count = Some(meta.holders_count.unwrap_or(0).saturating_add_signed(delta))
         });
-        count_list
     }
 
-    pub fn icrc7_tokens(&self, prev: Option<u128>, take: Option<u128>) -> Vec<u128> {
-        let mut take = take.unwrap_or(State::DEFAULT_TAKE_VALUE);
-        if take > self.icrc7_max_take_value().unwrap() {
-            ic_cdk::trap("Exceeds Max Take Value")
+    // Recounts the holders over up to `limit` keys of the owner index after
+    // `prev`, starting from zero when `prev` is `None`. Returns the last key
+    // counted if keys remain.
+    pub fn count_holders(
+        &mut self,
+        prev: Option<OwnerTokenKey>,
+        limit: usize,
+    ) -> Option<OwnerTokenKey> {
+        let mut last_owner = prev.as_ref().map(|key| key.owner);
+        let start = match prev {
+            Some(prev) => Excluded(prev),
+            None => Unbounded,
+        };
+        let batch: Vec<OwnerTokenKey> = self
+            .owner_index
+            .range((start, Unbounded))
+            .take(limit)
+            .map(|(key, _)| key)
+            .collect();
+        let mut holders = if last_owner.is_some() {
+            self.ledger_metadata.get().holders_count.unwrap_or(0)
+        } else {
+            0
+        };
+        for key in &batch {
+            if last_owner != Some(key.owner) {
+                holders += 1;
+                last_owner = Some(key.owner);
+            }
         }
+        self.update_ledger_metadata(|meta| meta.holders_count = Some(holders));
+        if batch.len() < limit {
+            None
+        } else {
+            batch.last().cloned()
+        }
+    }
+
+    // Adds up to `limit` tokens after `prev` to the owner index, and returns
+    // the last token id added if tokens remain.
+    pub fn index_token_owners(&mut self, prev: Option<u128>, limit: usize) -> Option<u128> {
+        let start = match prev {
+            Some(prev) => Excluded(prev),
+            None => Unbounded,
+        };
+        let batch: Vec<(u128, Account)> = self
+            .tokens
+            .range((start, Unbounded))
+            .take(limit)
+            .map(|(token_id, token)| (token_id, token.token_owner))
+            .collect();
+        let last = batch.last().map(|(token_id, _)| *token_id);
+        let is_last_batch = batch.len() < limit;
+        for (token_id, owner) in batch {
+            self.add_owner_key(OwnerTokenKey { owner, token_id });
+        }
+        if is_last_batch {
+            None
+        } else {
+            last
+        }
+    }
 
-        let mut list: Vec<u128> = self.tokens.iter().map(|(k, _)| k).collect();
-        list.sort();
+    // Token ids held by `account`, in ascending order.
+    fn owned_token_ids(&self, account: Account) -> impl Iterator<Item = u128> + '_ {
+        let owner = account_transformer(account);
+        self.owner_index
+            .range(
+                OwnerTokenKey { owner, token_id: 0 }..=OwnerTokenKey {
+                    owner,
+                    token_id: u128::MAX,
+                },
+            )
+            .map(|(key, _)| key.token_id)
+    }
 
-        take = std::cmp::min(take, list.len() as u128);
+    // Whether the state is at `STATE_VERSION`. Until then the owner and dedup
+    // indexes may be incomplete.
+    pub fn is_migrated(&self) -> bool {
+        self.migration.get().version >= STATE_VERSION
+    }
 
-        match prev {
-            Some(prev) => match list.iter().position(|id| *id == prev) {
-                None => vec![],
-                Some(index) => list
-                    .iter()
-                    .map(|id| *id)
-                    .skip(index)
-                    .take(take as usize)
-                    .collect(),
-            },
-            None => list[0..take as usize].to_vec(),
+    // Reads of the owner index trap until its migration is done, instead of
+    // answering from a partial index.
+    fn owner_index_guard(&self) {
+        if !self.is_migrated() {
+            ic_cdk::trap("State Migration Pending")
+        }
+    }
+
+    pub fn holders_count(&self) -> u64 {
+        self.owner_index_guard();
+        self.ledger_metadata.get().holders_count.unwrap_or(0)
+    }
+
+    pub fn icrc7_balance_of(&self, accounts: &[Account]) -> Vec<u128> {
+        self.owner_index_guard();
+        accounts
+            .iter()
+            .map(|account| self.owned_token_ids(*account).count() as u128)
+            .collect()
+    }
+
+    // Resolves the page size for `icrc7_tokens` and `icrc7_tokens_of`: a
+    // missing `take` falls back to the default take value, and a `take` above
+    // the max take value traps.
+    fn get_tokens_take(&self, take: Option<u128>) -> usize {
+        let max_take = self
+            .config
+            .get()
+            .icrc7_max_take_value
+            .unwrap_or(State::DEFAULT_MAX_TAKE_VALUE);
+        let default_take = self
+            .config
+            .get()
+            .icrc7_default_take_value
+            .unwrap_or(State::DEFAULT_TAKE_VALUE)
+            .min(max_take);
+        let take = take.unwrap_or(default_take);
+        if take > max_take {
+            ic_cdk::trap("Exceeds Max Take Value")
         }
+        take as usize
+    }
+
+    // Tokens are paged with an exclusive cursor: the page starts after `prev`,
+    // whether or not `prev` still exists.
+    pub fn icrc7_tokens(&self, prev: Option<u128>, take: Option<u128>) -> Vec<u128> {
+        let take = self.get_tokens_take(take);
+        let start = match prev {
+            Some(prev) => Excluded(prev),
+            None => Unbounded,
+        };
+        self.tokens
+            .range((start, Unbounded))
+            .take(take)
+            .map(|(id, _)| id)
+            .collect()
     }
 
     pub fn icrc7_tokens_of(
@@ -1520,30 +2034,22 @@ impl State {
         prev: Option<u128>,
         take: Option<u128>,
     ) -> Vec<u128> {
-        let take = take.unwrap_or(State::DEFAULT_TAKE_VALUE);
-        if take > State::DEFAULT_MAX_TAKE_VALUE {
-            ic_cdk::trap("Exceeds Max Take Value")
-        }
-        let mut owned_tokens = vec![];
-        for (id, token) in self.tokens.iter() {
-            if token.token_owner == account {
-                owned_tokens.push(id);
-            }
-        }
-        owned_tokens.sort();
-        let actual_take = std::cmp::min(take, owned_tokens.len() as u128);
-        match prev {
-            None => owned_tokens[0..actual_take as usize].to_vec(),
-            Some(prev) => match owned_tokens.iter().position(|id| *id == prev) {
-                None => vec![],
-                Some(index) => owned_tokens
-                    .iter()
-                    .map(|id| *id)
-                    .skip(index)
-                    .take(take as usize)
-                    .collect(),
-            },
-        }
+        self.owner_index_guard();
+        let take = self.get_tokens_take(take);
+        let owner = account_transformer(account);
+        let start = match prev {
+            Some(token_id) => Excluded(OwnerTokenKey { owner, token_id }),
+            None => Included(OwnerTokenKey { owner, token_id: 0 }),
+        };
+        let end = Included(OwnerTokenKey {
+            owner,
+            token_id: u128::MAX,
+        });
+        self.owner_index
+            .range((start, end))
+            .take(take)
+            .map(|(key, _)| key.token_id)
+            .collect()
     }
 
     pub fn icrc7_txn_logs(&self, page_number: u32, page_size: u32) -> Vec<Transaction> {
@@ -1586,8 +2092,8 @@ impl State {
 
     pub fn icrc3_get_blocks(&self, args: GetBlocksArgs) -> GetBlocksResult {
         let local_ledger_length = self.txn_ledger.len() as u128;
-        let local_first_index = self.archive_ledger_info.first_index;
-        let local_last_index = self.archive_ledger_info.last_index;
+        let local_first_index = self.archive_ledger_info.get().first_index;
+        let local_last_index = self.archive_ledger_info.get().last_index;
 
         let ledger_length = if local_last_index == 0 && local_ledger_length == 0 {
             0
@@ -1636,7 +2142,7 @@ impl State {
         //get any archive transactions
         for arg in args {
             let mut seeking = arg.start;
-            for (key, tran_range) in &self.archive_ledger_info.archives {
+            for (key, tran_range) in self.archives.iter() {
                 if (seeking > tran_range.start + tran_range.length - 1)
                     || (arg.start + arg.length <= tran_range.start)
                 {
@@ -1651,7 +2157,7 @@ impl State {
                 );
                 let overlap_length = overlap_end - overlap_start + 1;
 
-                match archived_blocks.get_mut(key) {
+                match archived_blocks.get_mut(&key) {
                     Some(archive) => {
                         archive.args.push(TransactionRange {
                             start: overlap_start,
@@ -1660,14 +2166,14 @@ impl State {
                     }
                     None => {
                         archived_blocks.insert(
-                            *key,
+                            key,
                             ArchivedTransactionResponse {
                                 args: vec![TransactionRange {
                                     start: overlap_start,
                                     length: overlap_length,
                                 }],
                                 callback: QueryTransactionsFn {
-                                    canister_id: *key,
+                                    canister_id: key,
                                     method: "get_transactions".to_string(),
                                     _marker: std::marker::PhantomData,
                                 },
@@ -1709,8 +2215,8 @@ impl State {
         if is_found {
             results.push(GetArchivesResultItem {
                 canister_id,
-                start: self.archive_ledger_info.first_index,
-                end: self.archive_ledger_info.last_index,
+                start: self.archive_ledger_info.get().first_index,
+                end: self.archive_ledger_info.get().last_index,
             })
         } else {
             if let Some(from) = arg.from {
@@ -1720,18 +2226,18 @@ impl State {
             }
         }
 
-        for (principal, range) in self.archive_ledger_info.archives.iter() {
+        for (principal, range) in self.archives.iter() {
             if is_found {
                 if range.start + range.length >= 1 {
                     results.push(GetArchivesResultItem {
-                        canister_id: *principal,
+                        canister_id: principal,
                         start: range.start,
                         end: range.start + range.length,
                     })
                 }
             } else {
                 if let Some(from) = arg.from {
-                    if from == *principal {
+                    if from == principal {
                         is_found = true;
                     }
                 }
@@ -1741,7 +2247,7 @@ impl State {
     }
 
     pub fn icrc3_get_tip(&self) -> Tip {
-        if self.archive_ledger_info.latest_hash.is_none() {
+        if self.archive_ledger_info.get().latest_hash.is_none() {
             ic_cdk::trap("No root")
         }
         let witness = TREE.with(|tree| {
@@ -1758,8 +2264,13 @@ impl State {
             witness
         });
         return Tip {
-            last_block_hash: self.archive_ledger_info.latest_hash.unwrap(),
-            last_block_index: self.archive_ledger_info.last_index.to_be_bytes().to_vec(),
+            last_block_hash: self.archive_ledger_info.get().latest_hash.unwrap(),
+            last_block_index: self
+                .archive_ledger_info
+                .get()
+                .last_index
+                .to_be_bytes()
+                .to_vec(),
             hash_tree: witness,
         };
     }
@@ -1779,8 +2290,10 @@ impl State {
         for txn_id in txn_ids {
             self.txn_ledger.remove(txn_id);
         }
-        self.sync_pending_txn_ids = None;
-        self.archive_txn_count += txn_ids.len() as u128;
+        self.set_sync_pending_txn_ids(None);
+        self.update_ledger_metadata(|meta| {
+            meta.archive_txn_count += txn_ids.len() as u128;
+        });
         return true;
     }
 
@@ -1793,7 +2306,7 @@ impl State {
     }
 
     pub fn add_archive(&mut self, canister_id: Principal, range: TransactionRange) -> bool {
-        self.archive_ledger_info.archives.insert(canister_id, range);
+        self.archives.insert(canister_id, range);
         return true;
     }
 }
@@ -1842,6 +2355,17 @@ async fn call_append_blocks(archive_log_canister: Principal, blocks: Vec<Block>)
     }
 }
 
+// How often entries that left the transaction window are dropped from the
+// dedup index.
+const DEDUP_EXPIRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub fn set_dedup_expiry_timer() {
+    let timer_id = ic_cdk_timers::set_timer_interval(DEDUP_EXPIRY_INTERVAL, || {
+        STATE.with(|s| s.borrow_mut().expire_txn_dedup(ic_cdk::api::time()));
+    });
+    TIMER_IDS.with(|timer_ids| timer_ids.borrow_mut().push(timer_id));
+}
+
 fn set_clean_up_timer() {
     // set Timer
     let secs = Duration::from_secs(10);
@@ -1857,8 +2381,8 @@ fn set_clean_up_timer() {
 
 async fn clean_local_ledger_task() {
     let txn_ledger_size = STATE.with(|s| s.borrow().txn_ledger.len());
-    let setting = STATE.with(|s| s.borrow().archive_ledger_info.setting.clone());
-    let local_first_index = STATE.with(|s| s.borrow().archive_ledger_info.first_index);
+    let setting = STATE.with(|s| s.borrow().archive_ledger_info.get().setting.clone());
+    let local_first_index = STATE.with(|s| s.borrow().archive_ledger_info.get().first_index);
     let max_active_records = setting.max_active_records;
     let max_records_in_archive_instance = setting.max_records_in_archive_instance;
     let max_records_to_archive = setting.max_records_to_archive;
@@ -1871,7 +2395,7 @@ async fn clean_local_ledger_task() {
 
     let mut is_recall_at_end = false;
 
-    let archive_count = STATE.with(|s| s.borrow().archive_ledger_info.archives.len());
+    let archive_count = STATE.with(|s| s.borrow().archives.len());
 
     if txn_ledger_size < max_active_records as u64 {
         ic_cdk::println!("clean_local_ledger_task: txn_ledger_size < max_active_records, don't clean if not necessary");
@@ -1883,7 +2407,7 @@ async fn clean_local_ledger_task() {
         return;
     }
 
-    STATE.with(|s: &RefCell<State>| s.borrow_mut().archive_ledger_info.is_cleaning = true);
+    STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(true));
     ic_cdk::println!("clean_local_ledger_task: Now we are cleaning");
 
     let mut last_archive: Option<(Principal, TransactionRange)> = None;
@@ -1920,24 +2444,15 @@ async fn clean_local_ledger_task() {
                     ic_cdk::println!(
                         "clean_local_ledger_task: create a new archive canister error"
                     );
-                    STATE.with(|s: &RefCell<State>| {
-                        s.borrow_mut().archive_ledger_info.is_cleaning = false
-                    });
+                    STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(false));
                 }
             }
         } else {
-            STATE.with(|s: &RefCell<State>| s.borrow_mut().archive_ledger_info.is_cleaning = false);
+            STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(false));
             return;
         }
     } else {
-        let current_last_archive = STATE.with(|s| {
-            s.borrow()
-                .archive_ledger_info
-                .archives
-                .clone()
-                .into_iter()
-                .last()
-        });
+        let current_last_archive = STATE.with(|s| s.borrow().archives.last_key_value());
 
         if let Some(current_last_archive) = current_last_archive {
             if current_last_archive.1.length >= max_records_in_archive_instance {
@@ -1972,14 +2487,12 @@ async fn clean_local_ledger_task() {
                                 "clean_local_ledger_task: create a new archive canister error"
                             );
                             STATE.with(|s: &RefCell<State>| {
-                                s.borrow_mut().archive_ledger_info.is_cleaning = false
+                                s.borrow_mut().set_archive_cleaning(false)
                             });
                         }
                     }
                 } else {
-                    STATE.with(|s: &RefCell<State>| {
-                        s.borrow_mut().archive_ledger_info.is_cleaning = false
-                    });
+                    STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(false));
                     return;
                 }
             } else {
@@ -2026,31 +2539,189 @@ async fn clean_local_ledger_task() {
         match call_result {
             Ok(_count) => {
                 STATE.with(|s| s.borrow_mut().remove_txn_logs(&to_archive_ids));
-                STATE.with(|s| s.borrow_mut().archive_ledger_info.first_index += to_archive_amount);
                 STATE.with(|s| {
-                    if let Some(transaction_range) = s
-                        .borrow_mut()
-                        .archive_ledger_info
-                        .archives
-                        .get_mut(&last_archive.0)
-                    {
+                    let mut s = s.borrow_mut();
+                    s.update_archive_ledger_info(|info| info.first_index += to_archive_amount);
+                    if let Some(mut transaction_range) = s.archives.get(&last_archive.0) {
                         transaction_range.length += to_archive_amount;
-                        transaction_range.start = transaction_range.start;
+                        s.archives.insert(last_archive.0, transaction_range);
                     }
                 });
             }
             Err(_) => {
-                STATE.with(|s: &RefCell<State>| {
-                    s.borrow_mut().archive_ledger_info.is_cleaning = false
-                });
+                STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(false));
                 ic_cdk::println!("clean_local_ledger_task: to_archive fail");
             }
         }
     }
 
-    STATE.with(|s: &RefCell<State>| s.borrow_mut().archive_ledger_info.is_cleaning = false);
+    STATE.with(|s: &RefCell<State>| s.borrow_mut().set_archive_cleaning(false));
 
     if is_recall_at_end {
         set_clean_up_timer()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(id: u8) -> Account {
+        account_transformer(Account {
+            owner: Principal::from_slice(&[id]),
+            subaccount: None,
+        })
+    }
+
+    fn mint(state: &mut State, token_id: u128, owner: Account) {
+        let token = Icrc7Token::new(
+            token_id,
+            format!("Token {}", token_id),
+            None,
+            None,
+            owner,
+            BTreeMap::new(),
+        );
+        state.tokens.insert(token_id, token);
+        state.index_token_owner(token_id, None, Some(owner));
+    }
+
+    fn burn(state: &mut State, token_id: u128) {
+        let token = state.tokens.remove(&token_id).unwrap();
+        state.index_token_owner(token_id, Some(token.token_owner), None);
+    }
+
+    fn transfer(state: &mut State, token_id: u128, to: Account) {
+        let mut token = state.tokens.get(&token_id).unwrap();
+        state.index_token_owner(token_id, Some(token.token_owner), Some(to));
+        token.token_owner = to;
+        state.tokens.insert(token_id, token);
+    }
+
+    // A fresh state reads as version 0, which gates the owner and dedup indexes.
+    fn migrated_state() -> State {
+        let mut state = State::default();
+        update_cell(&mut state.migration, |migration| {
+            migration.version = STATE_VERSION
+        });
+        state
+    }
+
+    fn set_take(state: &mut State, default_take: u128, max_take: u128) {
+        update_cell(&mut state.config, |config| {
+            config.icrc7_default_take_value = Some(default_take);
+            config.icrc7_max_take_value = Some(max_take);
+        });
+    }
+
+    #[test]
+    fn tokens_start_after_prev() {
+        let mut state = migrated_state();
+        for token_id in 1..=5 {
+            mint(&mut state, token_id, account(1));
+        }
+        assert_eq!(state.icrc7_tokens(None, None), vec![1, 2, 3, 4, 5]);
+        assert_eq!(state.icrc7_tokens(Some(2), None), vec![3, 4, 5]);
+        assert_eq!(state.icrc7_tokens(Some(5), None), Vec::<u128>::new());
+        assert_eq!(
+            state.icrc7_tokens_of(account(1), Some(2), None),
+            vec![3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn tokens_resume_after_a_burned_prev() {
+        let mut state = migrated_state();
+        for token_id in 1..=5 {
+            mint(&mut state, token_id, account(1));
+        }
+        burn(&mut state, 3);
+        assert_eq!(state.icrc7_tokens(Some(3), None), vec![4, 5]);
+        assert_eq!(state.icrc7_tokens_of(account(1), Some(3), None), vec![4, 5]);
+    }
+
+    #[test]
+    fn tokens_of_only_lists_the_account() {
+        let mut state = migrated_state();
+        for token_id in 1..=6 {
+            mint(&mut state, token_id, account(1 + (token_id % 2) as u8));
+        }
+        assert_eq!(state.icrc7_tokens_of(account(1), None, None), vec![2, 4, 6]);
+        assert_eq!(state.icrc7_tokens_of(account(2), Some(1), None), vec![3, 5]);
+        // A `prev` held by another account still pages from its position.
+        assert_eq!(state.icrc7_tokens_of(account(2), Some(2), None), vec![3, 5]);
+        assert_eq!(
+            state.icrc7_tokens_of(account(3), None, None),
+            Vec::<u128>::new()
+        );
+    }
+
+    #[test]
+    fn holders_follow_first_and_last_tokens() {
+        let mut state = migrated_state();
+        mint(&mut state, 1, account(1));
+        mint(&mut state, 2, account(1));
+        mint(&mut state, 3, account(2));
+        assert_eq!(state.holders_count(), 2);
+
+        burn(&mut state, 1);
+        assert_eq!(state.holders_count(), 2);
+        transfer(&mut state, 2, account(2));
+        assert_eq!(state.holders_count(), 1);
+        transfer(&mut state, 3, account(3));
+        assert_eq!(state.holders_count(), 2);
+        burn(&mut state, 2);
+        burn(&mut state, 3);
+        assert_eq!(state.holders_count(), 0);
+    }
+
+    #[test]
+    fn take_defaults_to_the_configured_value() {
+        let mut state = migrated_state();
+        for token_id in 1..=5 {
+            mint(&mut state, token_id, account(1));
+        }
+        set_take(&mut state, 2, 3);
+        assert_eq!(state.icrc7_tokens(None, None), vec![1, 2]);
+        assert_eq!(state.icrc7_tokens(None, Some(3)), vec![1, 2, 3]);
+        assert_eq!(state.icrc7_tokens_of(account(1), Some(1), None), vec![2, 3]);
+        // A default above the maximum is capped to it.
+        set_take(&mut state, 10, 3);
+        assert_eq!(state.icrc7_tokens(None, None), vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "trap")]
+    fn tokens_take_above_the_max_traps() {
+        let mut state = migrated_state();
+        set_take(&mut state, 2, 3);
+        state.icrc7_tokens(None, Some(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "trap")]
+    fn tokens_of_take_above_the_max_traps() {
+        let mut state = migrated_state();
+        set_take(&mut state, 2, 3);
+        state.icrc7_tokens_of(account(1), None, Some(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "trap")]
+    fn balance_of_traps_while_migrating() {
+        let mut state = State::default();
+        mint(&mut state, 1, account(1));
+        state.icrc7_balance_of(&[account(1)]);
+    }
+
+    #[test]
+    fn dedup_check_rejects_while_migrating() {
+        let state = State::default();
+        assert_eq!(
+            state.txn_deduplication_check(&[0; 32], 0, 0),
+            Err(TxnDedupError::Migrating)
+        );
+        let state = migrated_state();
+        assert_eq!(state.txn_deduplication_check(&[0; 32], 0, 0), Ok(()));
+    }
+}
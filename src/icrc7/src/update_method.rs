@@ -2,8 +2,9 @@ use candid::Principal;
 use ic_cdk_macros::update;
 
 use crate::{
-    guards::owner_guard, state::STATE, BurnArg, BurnResult, MintArg, MintResult, TransferArg,
-    TransferResult,
+    guards::{owner_guard, self_guard},
+    state::STATE,
+    BurnArg, BurnResult, MintArg, MintResult, TransferArg, TransferResult,
 };
 use icrc_ledger_types::icrc1::account::Account;
 
@@ -33,6 +34,18 @@ pub fn burn(args: Vec<BurnArg>) -> Vec<Option<BurnResult>> {
 
 #[update(guard = "owner_guard")]
 pub fn set_minting_authority(minting_account: Account) -> bool {
-    STATE.with(|s| s.borrow_mut().minting_authority = Some(minting_account));
+    STATE.with(|s| s.borrow_mut().set_minting_authority(minting_account));
     return true;
 }
+
+// Called by the migration timer, so that a step which traps is reported back
+// to it instead of ending the timer chain.
+#[update(guard = "self_guard")]
+pub fn migration_step() -> bool {
+    crate::migration::run_migration_step()
+}
+
+#[update(guard = "owner_guard")]
+pub fn resume_migration() -> bool {
+    crate::migration::resume_migration()
+}
@@ -1,6 +1,7 @@
-use candid::Principal;
+use candid::{Encode, Principal};
 use icrc_ledger_types::icrc::generic_value::{self, Value};
 use icrc_ledger_types::icrc1::account::{Account, Subaccount, DEFAULT_SUBACCOUNT};
+use sha2::{Digest, Sha256};
 
 pub fn account_transformer(account: Account) -> Account {
     if let Some(_) = account.subaccount {
@@ -37,3 +38,18 @@ pub fn burn_account() -> Account {
 pub fn hash_icrc_value(value: &Value) -> generic_value::Hash {
     return value.hash();
 }
+
+/// Hash identifying a transaction in the dedup index. `op` is the block type
+/// of the transaction, so identical arguments to different calls never
+/// collide.
+pub fn txn_dedup_hash(
+    op: &str,
+    caller: &Account,
+    token_id: Option<u128>,
+    to: Option<Account>,
+    memo: &Option<Vec<u8>>,
+    created_at_time: u64,
+) -> [u8; 32] {
+    let bytes = Encode!(&op, caller, &token_id, &to, memo, &created_at_time).unwrap();
+    Sha256::digest(bytes).into()
+}
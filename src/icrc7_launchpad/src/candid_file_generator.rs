@@ -0,0 +1,11 @@
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn save_candid() {
+        use std::env;
+        use std::fs::write;
+
+        let dir = env::current_dir().unwrap();
+        write(dir.join("icrc7_launchpad.did"), crate::__export_service()).expect("Write failed.");
+    }
+}
@@ -0,0 +1,329 @@
+use candid::{Encode, Nat, Principal};
+use ic_cdk::api::management_canister::{
+    main::{
+        canister_status, create_canister, deposit_cycles, install_code, uninstall_code,
+        update_settings, CanisterIdRecord, CanisterInstallMode, CreateCanisterArgument,
+        InstallCodeArgument, UpdateSettingsArgument,
+    },
+    provisional::CanisterSettings,
+};
+
+use icrc7_types::errors::DeployError;
+
+use crate::{
+    state::STATE,
+    types::{
+        CollectionMetadata, CollectionSettings, ControlStatus, ControllerPolicy, DeploymentStatus,
+    },
+};
+
+pub const COLLECTION_DEFAULT_CYCLES: u128 = 10_000_000_000_000;
+pub const COLLECTION_MIN_CYCLES: u128 = 1_000_000_000_000;
+pub const COLLECTION_MAX_CYCLES: u128 = 100_000_000_000_000;
+pub const COLLECTION_MAX_TOP_UP_CYCLES: u128 = 10_000_000_000_000;
+pub const CREATOR_MAX_TOP_UP_CYCLES: u128 = 50_000_000_000_000;
+
+// System defaults applied to the settings a creator leaves unset.
+pub const SETTINGS_DEFAULT_COMPUTE_ALLOCATION: u64 = 0;
+pub const SETTINGS_DEFAULT_MEMORY_ALLOCATION: u64 = 0;
+pub const SETTINGS_DEFAULT_FREEZING_THRESHOLD: u64 = 2_592_000;
+pub const SETTINGS_DEFAULT_RESERVED_CYCLES_LIMIT: u128 = 5_000_000_000_000;
+
+pub const SETTINGS_DEFAULT_MAX_MEMORY_ALLOCATION: u64 = 4 * 1024 * 1024 * 1024;
+pub const SETTINGS_DEFAULT_MAX_FREEZING_THRESHOLD: u64 = 7_776_000;
+pub const SETTINGS_DEFAULT_MAX_RESERVED_CYCLES_LIMIT: u128 = 10_000_000_000_000;
+
+// Every field is set so that a reused spare canister does not keep the
+// settings of its previous deployment.
+fn collection_settings(creator: Principal, settings: &CollectionSettings) -> CanisterSettings {
+    CanisterSettings {
+        controllers: Some(vec![ic_cdk::id(), creator]),
+        compute_allocation: settings.compute_allocation.map(Nat::from),
+        memory_allocation: settings.memory_allocation.map(Nat::from),
+        freezing_threshold: settings.freezing_threshold.map(Nat::from),
+        reserved_cycles_limit: settings.reserved_cycles_limit.map(Nat::from),
+    }
+}
+
+pub async fn create_collection_canister(
+    creator: Principal,
+    cycles: u128,
+    settings: &CollectionSettings,
+) -> Result<Principal, DeployError> {
+    if cycles > ic_cdk::api::canister_balance128() {
+        return Err(DeployError::InsufficientCycles);
+    }
+    match create_canister(
+        CreateCanisterArgument {
+            settings: Some(collection_settings(creator, settings)),
+        },
+        cycles,
+    )
+    .await
+    {
+        Err((code, msg)) => Err(DeployError::CreateFailed {
+            code: code as i32,
+            msg,
+        }),
+        Ok((principal,)) => {
+            STATE.with(|s| s.borrow_mut().record_deploy_cycles(creator, cycles));
+            Ok(principal.canister_id)
+        }
+    }
+}
+
+/// Returns an empty canister for `creator` holding at least `cycles`, reusing a
+/// reclaimed one when available. A spare that cannot be reused goes back to
+/// the pool.
+pub async fn acquire_collection_canister(
+    creator: Principal,
+    cycles: u128,
+    settings: &CollectionSettings,
+) -> Result<Principal, DeployError> {
+    if let Some(canister_id) = STATE.with(|s| s.borrow_mut().take_spare_canister()) {
+        match reuse_spare_canister(canister_id, creator, cycles, settings).await {
+            Ok(()) => return Ok(canister_id),
+            Err(e) => {
+                ic_cdk::println!(
+                    "acquire_collection_canister: cannot reuse {}: {}",
+                    canister_id,
+                    e
+                );
+                STATE.with(|s| s.borrow_mut().add_spare_canister(canister_id));
+            }
+        }
+    }
+    create_collection_canister(creator, cycles, settings).await
+}
+
+// Tops a spare canister up to `cycles` and hands it to `creator`. The top-up
+// is billed to the creator only once the canister is theirs; until then the
+// cycles stay with the spare.
+async fn reuse_spare_canister(
+    canister_id: Principal,
+    creator: Principal,
+    cycles: u128,
+    settings: &CollectionSettings,
+) -> Result<(), String> {
+    let balance = match canister_status(CanisterIdRecord { canister_id }).await {
+        Ok((status,)) => u128::try_from(status.cycles.0).unwrap_or(u128::MAX),
+        Err((code, msg)) => return Err(format!("Code: {:?}, Message: {:?}", code, msg)),
+    };
+    let top_up = cycles.saturating_sub(balance);
+    if top_up > 0 {
+        if top_up > ic_cdk::api::canister_balance128() {
+            return Err("Insufficient launchpad cycles balance".into());
+        }
+        top_up_collection_canister(canister_id, top_up).await?;
+    }
+    if let Err((code, msg)) = update_settings(UpdateSettingsArgument {
+        canister_id,
+        settings: collection_settings(creator, settings),
+    })
+    .await
+    {
+        return Err(format!("Code: {:?}, Message: {:?}", code, msg));
+    }
+    STATE.with(|s| s.borrow_mut().record_deploy_cycles(creator, top_up));
+    Ok(())
+}
+
+pub async fn top_up_collection_canister(
+    canister_id: Principal,
+    cycles: u128,
+) -> Result<(), String> {
+    match deposit_cycles(CanisterIdRecord { canister_id }, cycles).await {
+        Ok(()) => Ok(()),
+        Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+pub async fn collection_controllers(canister_id: Principal) -> Result<Vec<Principal>, String> {
+    match canister_status(CanisterIdRecord { canister_id }).await {
+        Ok((status,)) => Ok(status.settings.controllers),
+        Err((code, msg)) => Err(format!("Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+pub async fn set_collection_controllers(
+    canister_id: Principal,
+    controllers: Vec<Principal>,
+) -> Result<(), String> {
+    match update_settings(UpdateSettingsArgument {
+        canister_id,
+        settings: CanisterSettings {
+            controllers: Some(controllers),
+            compute_allocation: None,
+            memory_allocation: None,
+            freezing_threshold: None,
+            reserved_cycles_limit: None,
+        },
+    })
+    .await
+    {
+        Ok(()) => Ok(()),
+        Err((code, msg)) => Err(format!("Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+/// Replaces the deploy-time controllers with the ones requested by `policy`,
+/// plus the co-owners.
+async fn hand_off_collection_canister(
+    canister_id: Principal,
+    creator: Principal,
+    policy: ControllerPolicy,
+    co_owners: &[Principal],
+) -> Result<ControlStatus, String> {
+    let launchpad = ic_cdk::id();
+    let controllers = policy.controllers_with(launchpad, creator, co_owners);
+    if policy != ControllerPolicy::CreatorAndLaunchpad || !co_owners.is_empty() {
+        set_collection_controllers(canister_id, controllers.clone())
+            .await
+            .map_err(|e| format!("Controller handoff failed: {}", e))?;
+    }
+    Ok(ControlStatus::from_controllers(launchpad, controllers))
+}
+
+pub async fn uninstall_collection_canister(canister_id: Principal) -> Result<(), String> {
+    match uninstall_code(CanisterIdRecord { canister_id }).await {
+        Ok(()) => Ok(()),
+        Err((code, msg)) => Err(format!("Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+pub async fn upgrade_collection_canister(
+    canister_id: Principal,
+    wasm_module: Vec<u8>,
+) -> Result<(), String> {
+    match install_code(InstallCodeArgument {
+        mode: CanisterInstallMode::Upgrade(None),
+        canister_id,
+        wasm_module,
+        arg: Encode!().unwrap(),
+    })
+    .await
+    {
+        Ok(()) => Ok(()),
+        Err((code, msg)) => Err(format!("Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+pub async fn query_collection<T: candid::CandidType + for<'de> candid::Deserialize<'de>>(
+    canister_id: Principal,
+    method: &str,
+) -> Result<T, String> {
+    let call_result: Result<(T,), _> = ic_cdk::call(canister_id, method, ()).await;
+    match call_result {
+        Ok((value,)) => Ok(value),
+        Err((code, msg)) => Err(format!(
+            "{} failed: Rejection Code: {:?}, Message: {:?}",
+            method, code, msg
+        )),
+    }
+}
+
+pub async fn fetch_collection_metadata(
+    canister_id: Principal,
+) -> Result<CollectionMetadata, String> {
+    Ok(CollectionMetadata {
+        icrc7_symbol: query_collection(canister_id, "icrc7_symbol").await?,
+        icrc7_name: query_collection(canister_id, "icrc7_name").await?,
+        icrc7_description: query_collection(canister_id, "icrc7_description").await?,
+        icrc7_logo: query_collection(canister_id, "icrc7_logo").await?,
+    })
+}
+
+async fn verify_collection_canister(canister_id: Principal, symbol: &str) -> Result<(), String> {
+    let call_result: Result<(String,), _> = ic_cdk::call(canister_id, "icrc7_symbol", ()).await;
+    match call_result {
+        Ok((installed_symbol,)) if installed_symbol == symbol => Ok(()),
+        Ok((installed_symbol,)) => Err(format!(
+            "Verification failed: expected symbol {}, got {}",
+            symbol, installed_symbol
+        )),
+        Err((code, msg)) => Err(format!(
+            "Verification failed: Rejection Code: {:?}, Message: {:?}",
+            code, msg
+        )),
+    }
+}
+
+/// Installs and verifies the deployment recorded for `canister_id`, moving it
+/// through `Created -> Installed -> Verified`, or to `Failed`. The requested
+/// controllers are only applied once the collection is verified.
+pub async fn run_deployment(
+    canister_id: Principal,
+    mode: CanisterInstallMode,
+    wasm_version: String,
+    wasm_module: Vec<u8>,
+) -> Result<(), DeployError> {
+    let deployment = STATE
+        .with(|s| {
+            s.borrow_mut()
+                .start_deployment_attempt(&canister_id, wasm_version)
+        })
+        .ok_or_else(|| DeployError::UnknownDeployment {
+            message: "Unknown deployment".into(),
+        })?;
+
+    if let Err((code, msg)) = install_code(InstallCodeArgument {
+        mode,
+        canister_id,
+        wasm_module,
+        arg: deployment.init_arg.into_vec(),
+    })
+    .await
+    {
+        let message = format!("Code: {:?}, Message: {:?}", code, msg);
+        STATE.with(|s| {
+            s.borrow_mut().set_deployment_status(
+                &canister_id,
+                DeploymentStatus::Failed {
+                    message: message.clone(),
+                },
+            )
+        });
+        return Err(DeployError::InstallFailed {
+            canister_id,
+            code: code as i32,
+            msg,
+        });
+    }
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set_deployment_status(&canister_id, DeploymentStatus::Installed)
+    });
+
+    let verified = match verify_collection_canister(canister_id, &deployment.icrc7_symbol).await {
+        Ok(()) => {
+            hand_off_collection_canister(
+                canister_id,
+                deployment.creator,
+                deployment.controller_policy.unwrap_or_default(),
+                &deployment.co_owners.unwrap_or_default(),
+            )
+            .await
+        }
+        Err(message) => Err(message),
+    };
+    match verified {
+        Ok(control) => {
+            STATE.with(|s| s.borrow_mut().complete_deployment(&canister_id, control));
+            Ok(())
+        }
+        Err(message) => {
+            STATE.with(|s| {
+                s.borrow_mut().set_deployment_status(
+                    &canister_id,
+                    DeploymentStatus::Failed {
+                        message: message.clone(),
+                    },
+                )
+            });
+            Err(DeployError::VerificationFailed {
+                canister_id,
+                message,
+            })
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::{
+    icrc::generic_value::Value,
+    icrc1::{
+        account::Account,
+        transfer::{TransferArg, TransferError},
+    },
+    icrc2::transfer_from::{TransferFromArgs, TransferFromError},
+};
+
+use crate::{
+    icrc3::{account_value, principal_value, tx, BTYPE_FEE, BTYPE_REFUND, BTYPE_SETTLE},
+    state::STATE,
+    types::{DeploymentFee, FailedFeeTransfer, FeeTransferKind},
+};
+
+/// The subset of ICRC-1/ICRC-2 ledger calls needed to charge deployment fees.
+pub(crate) trait Ledger {
+    async fn fee(&self) -> Result<u128, String>;
+
+    async fn transfer_from(&self, from: Account, to: Account, amount: u128)
+        -> Result<u128, String>;
+
+    async fn transfer(&self, to: Account, amount: u128) -> Result<u128, String>;
+}
+
+pub(crate) struct IcrcLedger(pub Principal);
+
+fn nat_to_u128(n: Nat) -> u128 {
+    u128::try_from(n.0).unwrap_or(u128::MAX)
+}
+
+impl Ledger for IcrcLedger {
+    async fn fee(&self) -> Result<u128, String> {
+        let call_result: Result<(Nat,), _> = ic_cdk::call(self.0, "icrc1_fee", ()).await;
+        match call_result {
+            Ok((fee,)) => Ok(nat_to_u128(fee)),
+            Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+        }
+    }
+
+    async fn transfer_from(
+        &self,
+        from: Account,
+        to: Account,
+        amount: u128,
+    ) -> Result<u128, String> {
+        let arg = TransferFromArgs {
+            spender_subaccount: None,
+            from,
+            to,
+            amount: Nat::from(amount),
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        };
+        let call_result: Result<(Result<Nat, TransferFromError>,), _> =
+            ic_cdk::call(self.0, "icrc2_transfer_from", (arg,)).await;
+        match call_result {
+            Ok((Ok(block_index),)) => Ok(nat_to_u128(block_index)),
+            Ok((Err(e),)) => Err(format!("Transfer From Error: {:?}", e)),
+            Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+        }
+    }
+
+    async fn transfer(&self, to: Account, amount: u128) -> Result<u128, String> {
+        let arg = TransferArg {
+            from_subaccount: None,
+            to,
+            fee: None,
+            created_at_time: None,
+            memo: None,
+            amount: Nat::from(amount),
+        };
+        let call_result: Result<(Result<Nat, TransferError>,), _> =
+            ic_cdk::call(self.0, "icrc1_transfer", (arg,)).await;
+        match call_result {
+            Ok((Ok(block_index),)) => Ok(nat_to_u128(block_index)),
+            Ok((Err(e),)) => Err(format!("Transfer Error: {:?}", e)),
+            Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+        }
+    }
+}
+
+/// Pulls the deployment fee from `payer` into the launchpad's `escrow` account.
+pub(crate) async fn collect_fee<L: Ledger>(
+    ledger: &L,
+    fee: &DeploymentFee,
+    payer: Account,
+    escrow: Account,
+) -> Result<u128, String> {
+    ledger.transfer_from(payer, escrow, fee.amount).await
+}
+
+// Sends the escrowed fee, minus the ledger's own transfer fee, to `to`.
+async fn release_fee<L: Ledger>(
+    ledger: &L,
+    fee: &DeploymentFee,
+    to: Account,
+) -> Result<u128, String> {
+    let ledger_fee = ledger.fee().await?;
+    if fee.amount <= ledger_fee {
+        return Err("Deployment fee does not cover the ledger fee".into());
+    }
+    ledger.transfer(to, fee.amount - ledger_fee).await
+}
+
+/// Returns an escrowed fee to the payer after a failed deployment.
+pub(crate) async fn refund_fee<L: Ledger>(
+    ledger: &L,
+    fee: &DeploymentFee,
+    payer: Account,
+) -> Result<u128, String> {
+    release_fee(ledger, fee, payer).await
+}
+
+/// Forwards an escrowed fee to the treasury after a successful deployment.
+pub(crate) async fn settle_fee<L: Ledger>(ledger: &L, fee: &DeploymentFee) -> Result<u128, String> {
+    release_fee(ledger, fee, fee.treasury).await
+}
+
+// Records a fee movement in the launchpad log, `block` is the ledger block index.
+fn log_fee(btype: &str, fee: &DeploymentFee, account: Account, block: u128) {
+    STATE.with(|s| {
+        s.borrow_mut().append_block(
+            btype,
+            tx([
+                ("account", account_value(account)),
+                ("ledger_id", principal_value(fee.ledger_id)),
+                ("amount", Value::Nat(fee.amount.into())),
+                ("ledger_block", Value::Nat(block.into())),
+            ]),
+        )
+    });
+}
+
+/// Charges the configured deployment fee, if any, and returns it so that it
+/// can be settled or refunded once the deployment outcome is known.
+pub(crate) async fn charge_deployment_fee(payer: Account) -> Result<Option<DeploymentFee>, String> {
+    let fee = STATE.with(|s| s.borrow().deployment_fee());
+    if let Some(ref fee) = fee {
+        let escrow = Account::from(ic_cdk::id());
+        let block = collect_fee(&IcrcLedger(fee.ledger_id), fee, payer, escrow)
+            .await
+            .map_err(|e| format!("Deployment fee payment failed: {}", e))?;
+        log_fee(BTYPE_FEE, fee, payer, block);
+    }
+    Ok(fee)
+}
+
+pub(crate) async fn refund_deployment_fee(fee: Option<DeploymentFee>, payer: Account) {
+    if let Some(fee) = fee {
+        match refund_fee(&IcrcLedger(fee.ledger_id), &fee, payer).await {
+            Ok(block) => log_fee(BTYPE_REFUND, &fee, payer, block),
+            Err(e) => record_failed_transfer(FeeTransferKind::Refund, fee, payer, e),
+        }
+    }
+}
+
+pub(crate) async fn settle_deployment_fee(fee: Option<DeploymentFee>) {
+    if let Some(fee) = fee {
+        match settle_fee(&IcrcLedger(fee.ledger_id), &fee).await {
+            Ok(block) => log_fee(BTYPE_SETTLE, &fee, fee.treasury, block),
+            Err(e) => {
+                let treasury = fee.treasury;
+                record_failed_transfer(FeeTransferKind::Settlement, fee, treasury, e)
+            }
+        }
+    }
+}
+
+// Keeps a fee that is stuck in escrow so that an admin can retry it.
+fn record_failed_transfer(kind: FeeTransferKind, fee: DeploymentFee, to: Account, error: String) {
+    STATE.with(|s| {
+        s.borrow_mut()
+            .record_failed_fee_transfer(kind, fee, to, error)
+    });
+}
+
+/// Sends a failed refund or settlement out of escrow again.
+pub(crate) async fn release_failed_transfer<L: Ledger>(
+    ledger: &L,
+    transfer: &FailedFeeTransfer,
+) -> Result<u128, String> {
+    match transfer.kind {
+        FeeTransferKind::Refund => refund_fee(ledger, &transfer.fee, transfer.to).await,
+        FeeTransferKind::Settlement => settle_fee(ledger, &transfer.fee).await,
+    }
+}
+
+pub(crate) async fn retry_failed_fee_transfer(id: u64) -> Result<u128, String> {
+    let transfer = STATE
+        .with(|s| s.borrow_mut().take_failed_fee_transfer(id))
+        .ok_or_else(|| String::from("Unknown fee transfer"))?;
+    match release_failed_transfer(&IcrcLedger(transfer.fee.ledger_id), &transfer).await {
+        Ok(block) => {
+            let btype = match transfer.kind {
+                FeeTransferKind::Refund => BTYPE_REFUND,
+                FeeTransferKind::Settlement => BTYPE_SETTLE,
+            };
+            log_fee(btype, &transfer.fee, transfer.to, block);
+            Ok(block)
+        }
+        Err(e) => {
+            STATE.with(|s| {
+                s.borrow_mut()
+                    .restore_failed_fee_transfer(transfer, e.clone())
+            });
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    // An in-memory stand-in for an ICRC-2 ledger. The launchpad is the spender
+    // and the escrow owner, so allowances are tracked per payer only.
+    struct StandInLedger {
+        fee: u128,
+        escrow: Account,
+        balances: RefCell<BTreeMap<Account, u128>>,
+        allowances: RefCell<BTreeMap<Account, u128>>,
+        next_block: RefCell<u128>,
+    }
+
+    impl StandInLedger {
+        fn new(fee: u128, escrow: Account) -> Self {
+            Self {
+                fee,
+                escrow,
+                balances: RefCell::default(),
+                allowances: RefCell::default(),
+                next_block: RefCell::default(),
+            }
+        }
+
+        fn mint(&self, to: Account, amount: u128) {
+            *self.balances.borrow_mut().entry(to).or_default() += amount;
+        }
+
+        fn approve(&self, from: Account, amount: u128) {
+            self.allowances.borrow_mut().insert(from, amount);
+        }
+
+        fn balance_of(&self, account: Account) -> u128 {
+            self.balances.borrow().get(&account).copied().unwrap_or(0)
+        }
+
+        fn move_funds(&self, from: Account, to: Account, amount: u128) -> Result<u128, String> {
+            let mut balances = self.balances.borrow_mut();
+            let balance = balances.get(&from).copied().unwrap_or(0);
+            if balance < amount + self.fee {
+                return Err("InsufficientFunds".into());
+            }
+            balances.insert(from, balance - amount - self.fee);
+            *balances.entry(to).or_default() += amount;
+            let mut next_block = self.next_block.borrow_mut();
+            *next_block += 1;
+            Ok(*next_block - 1)
+        }
+    }
+
+    impl Ledger for StandInLedger {
+        async fn fee(&self) -> Result<u128, String> {
+            Ok(self.fee)
+        }
+
+        async fn transfer_from(
+            &self,
+            from: Account,
+            to: Account,
+            amount: u128,
+        ) -> Result<u128, String> {
+            let allowance = self.allowances.borrow().get(&from).copied().unwrap_or(0);
+            if allowance < amount + self.fee {
+                return Err("InsufficientAllowance".into());
+            }
+            let block_index = self.move_funds(from, to, amount)?;
+            self.allowances
+                .borrow_mut()
+                .insert(from, allowance - amount - self.fee);
+            Ok(block_index)
+        }
+
+        async fn transfer(&self, to: Account, amount: u128) -> Result<u128, String> {
+            self.move_funds(self.escrow, to, amount)
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn account(id: u8) -> Account {
+        Account::from(Principal::from_slice(&[id]))
+    }
+
+    fn setup() -> (StandInLedger, DeploymentFee, Account) {
+        let escrow = account(1);
+        let ledger = StandInLedger::new(10, escrow);
+        let fee = DeploymentFee {
+            ledger_id: Principal::anonymous(),
+            amount: 1_000,
+            treasury: account(2),
+        };
+        let payer = account(3);
+        ledger.mint(payer, 5_000);
+        (ledger, fee, payer)
+    }
+
+    #[test]
+    fn collect_and_settle_fee() {
+        let (ledger, fee, payer) = setup();
+        ledger.approve(payer, 1_010);
+
+        block_on(collect_fee(&ledger, &fee, payer, ledger.escrow)).unwrap();
+        assert_eq!(ledger.balance_of(payer), 3_990);
+        assert_eq!(ledger.balance_of(ledger.escrow), 1_000);
+
+        block_on(settle_fee(&ledger, &fee)).unwrap();
+        assert_eq!(ledger.balance_of(ledger.escrow), 0);
+        assert_eq!(ledger.balance_of(fee.treasury), 990);
+    }
+
+    #[test]
+    fn collect_fee_requires_allowance() {
+        let (ledger, fee, payer) = setup();
+        ledger.approve(payer, 500);
+
+        assert!(block_on(collect_fee(&ledger, &fee, payer, ledger.escrow)).is_err());
+        assert_eq!(ledger.balance_of(payer), 5_000);
+    }
+
+    #[test]
+    fn refund_fee_returns_escrow_to_payer() {
+        let (ledger, fee, payer) = setup();
+        ledger.approve(payer, 1_010);
+
+        block_on(collect_fee(&ledger, &fee, payer, ledger.escrow)).unwrap();
+        block_on(refund_fee(&ledger, &fee, payer)).unwrap();
+        assert_eq!(ledger.balance_of(ledger.escrow), 0);
+        assert_eq!(ledger.balance_of(payer), 4_980);
+        assert_eq!(ledger.balance_of(fee.treasury), 0);
+    }
+
+    #[test]
+    fn failed_transfers_are_released_to_their_recipient() {
+        let (ledger, fee, payer) = setup();
+        ledger.approve(payer, 2_020);
+        block_on(collect_fee(&ledger, &fee, payer, ledger.escrow)).unwrap();
+        block_on(collect_fee(&ledger, &fee, payer, ledger.escrow)).unwrap();
+
+        let transfer = |kind, to| FailedFeeTransfer {
+            id: 0,
+            kind,
+            fee: fee.clone(),
+            to,
+            error: "TemporarilyUnavailable".into(),
+            attempts: 1,
+            created_at: 0,
+            updated_at: 0,
+        };
+        block_on(release_failed_transfer(
+            &ledger,
+            &transfer(FeeTransferKind::Refund, payer),
+        ))
+        .unwrap();
+        block_on(release_failed_transfer(
+            &ledger,
+            &transfer(FeeTransferKind::Settlement, fee.treasury),
+        ))
+        .unwrap();
+        assert_eq!(ledger.balance_of(ledger.escrow), 0);
+        assert_eq!(ledger.balance_of(payer), 3_970);
+        assert_eq!(ledger.balance_of(fee.treasury), 990);
+    }
+}
@@ -0,0 +1,66 @@
+use candid::Principal;
+use ic_cdk::caller;
+use icrc7_types::errors::DeployError;
+
+use crate::state::STATE;
+
+// Controllers are always admins, so the launchpad cannot lock itself out.
+#[inline(always)]
+pub fn controller_guard() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&caller()) {
+        Ok(())
+    } else {
+        Err(String::from(
+            "The caller is not a controller of the launchpad",
+        ))
+    }
+}
+
+/// Whether `principal` is an admin or a controller of the launchpad.
+pub fn is_admin(principal: &Principal) -> bool {
+    STATE.with(|s| s.borrow().is_admin(principal)) || ic_cdk::api::is_controller(principal)
+}
+
+#[inline(always)]
+pub fn admin_guard() -> Result<(), String> {
+    if is_admin(&caller()) {
+        Ok(())
+    } else {
+        Err(String::from("The caller is not an admin of the launchpad"))
+    }
+}
+
+#[inline(always)]
+pub fn authenticated_guard() -> Result<(), String> {
+    if caller() == Principal::anonymous() {
+        Err("anonymous user is not allowed".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[inline(always)]
+pub fn creator_guard() -> Result<(), String> {
+    check_creator(caller()).map_err(|e| e.to_string())
+}
+
+/// Checks that `caller` may deploy collections. Deployment endpoints call it
+/// themselves so that clients can match on the error.
+pub fn check_creator(caller: Principal) -> Result<(), DeployError> {
+    if caller == Principal::anonymous() {
+        return Err(DeployError::AnonymousCaller);
+    }
+    if is_admin(&caller) {
+        return Ok(());
+    }
+    STATE.with(|s| {
+        let s = s.borrow();
+        if s.deployments_paused {
+            return Err(DeployError::Paused);
+        }
+        if s.allowlist_enabled && !s.is_allowlisted(&caller) {
+            return Err(DeployError::Unauthorized);
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use ic_certified_map::{AsHashTree, Hash, RbTree};
+use icrc7_types::icrc3_types::BlockType;
+use icrc_ledger_types::{
+    icrc::generic_value::{Map, Value},
+    icrc1::account::Account,
+};
+use serde::Serialize;
+
+pub const BTYPE_DEPLOY: &str = "lp_deploy";
+pub const BTYPE_UPGRADE: &str = "lp_upgrade";
+pub const BTYPE_TOP_UP: &str = "lp_topup";
+pub const BTYPE_FEE: &str = "lp_fee";
+pub const BTYPE_REFUND: &str = "lp_refund";
+pub const BTYPE_SETTLE: &str = "lp_settle";
+pub const BTYPE_CONTROL: &str = "lp_control";
+
+const BLOCK_TYPES_URL: &str = "https://github.com/tuminfei/icrc7_launchpad/blob/main/README.md";
+
+thread_local! {
+    // Leaves hold the raw tip values, as ICRC-3 clients expect them.
+    static TREE: RefCell<RbTree<&'static str, Vec<u8>>> = const { RefCell::new(RbTree::new()) };
+}
+
+pub fn supported_block_types() -> Vec<BlockType> {
+    [
+        BTYPE_DEPLOY,
+        BTYPE_UPGRADE,
+        BTYPE_TOP_UP,
+        BTYPE_FEE,
+        BTYPE_REFUND,
+        BTYPE_SETTLE,
+        BTYPE_CONTROL,
+    ]
+    .into_iter()
+    .map(|block_type| BlockType {
+        block_type: block_type.into(),
+        url: BLOCK_TYPES_URL.into(),
+    })
+    .collect()
+}
+
+pub fn principal_value(principal: Principal) -> Value {
+    Value::blob(principal.as_slice())
+}
+
+pub fn account_value(Account { owner, subaccount }: Account) -> Value {
+    let mut parts = vec![Value::blob(owner.as_slice())];
+    if let Some(subaccount) = subaccount {
+        parts.push(Value::blob(subaccount.as_slice()));
+    }
+    Value::Array(parts)
+}
+
+/// Builds the `tx` map of a launchpad block from its fields.
+pub fn tx<const N: usize>(fields: [(&str, Value); N]) -> Map {
+    fields
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+// ICRC-3 certifies the last block index as a leb128 encoded Nat.
+pub fn leb128(mut n: u128) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Certifies `last_block_index` and `last_block_hash` as the log tip.
+pub fn certify_tip(last_block_index: u128, last_block_hash: Hash) {
+    TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        tree.insert("last_block_index", leb128(last_block_index));
+        tree.insert("last_block_hash", last_block_hash.to_vec());
+        ic_cdk::api::set_certified_data(&tree.root_hash());
+    });
+}
+
+/// A single hash tree revealing both certified tip labels.
+pub fn tip_witness() -> Vec<u8> {
+    TREE.with(|tree| {
+        let tree = tree.borrow();
+        let mut witness = vec![];
+        let mut witness_serializer = serde_cbor::Serializer::new(&mut witness);
+        let _ = witness_serializer.self_describe();
+        tree.value_range(b"last_block_hash", b"last_block_index")
+            .serialize(&mut witness_serializer)
+            .unwrap();
+        witness
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_block_index_as_leb128() {
+        assert_eq!(leb128(0), vec![0x00]);
+        assert_eq!(leb128(127), vec![0x7f]);
+        assert_eq!(leb128(128), vec![0x80, 0x01]);
+        assert_eq!(leb128(624_485), vec![0xe5, 0x8e, 0x26]);
+    }
+}
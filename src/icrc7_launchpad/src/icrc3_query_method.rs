@@ -0,0 +1,42 @@
+use ic_cdk_macros::query;
+use icrc7_types::icrc3_types::{
+    BlockType, GetArchiveArgs, GetArchivesResultItem, GetBlocksArgs, GetBlocksResult,
+};
+use icrc_ledger_types::icrc3::blocks::DataCertificate;
+use serde_bytes::ByteBuf;
+
+use crate::{
+    icrc3::{supported_block_types, tip_witness},
+    state::STATE,
+};
+
+// Returns all the supported block types.
+#[query]
+pub fn icrc3_supported_block_types() -> Vec<BlockType> {
+    supported_block_types()
+}
+
+// The launchpad keeps its whole log, there are no archives.
+#[query]
+pub fn icrc3_get_archives(_arg: GetArchiveArgs) -> Vec<GetArchivesResultItem> {
+    vec![]
+}
+
+// Certifies the last launchpad block, `None` while the log is empty.
+#[query]
+pub fn icrc3_get_tip_certificate() -> Option<DataCertificate> {
+    if STATE.with(|s| s.borrow().log_length()) == 0 {
+        return None;
+    }
+    let certificate = ic_cdk::api::data_certificate()?;
+    Some(DataCertificate {
+        certificate: Some(ByteBuf::from(certificate)),
+        hash_tree: ByteBuf::from(tip_witness()),
+    })
+}
+
+// Get icrc3 blocks information
+#[query]
+pub fn icrc3_get_blocks(args: GetBlocksArgs) -> GetBlocksResult {
+    STATE.with(|s| s.borrow().icrc3_get_blocks(args))
+}
@@ -0,0 +1,76 @@
+use ic_cdk_macros::{init, post_upgrade, pre_upgrade};
+use ic_stable_structures::{writer::Writer, Memory};
+
+use crate::{
+    mint::set_mint_timer, monitor::set_monitor_timer, state::STATE, stats::set_stats_timer,
+    upgrade::set_upgrade_timer,
+};
+
+#[init]
+fn init() {
+    set_monitor_timer();
+    set_stats_timer();
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    // Serialize the state.
+    let mut state_bytes = vec![];
+    STATE
+        .with(|s| ciborium::ser::into_writer(&*s.borrow(), &mut state_bytes))
+        .expect("failed to encode state");
+
+    // Write the length of the serialized bytes to memory, followed by the
+    // by the bytes themselves.
+    let len = state_bytes.len() as u32;
+    let mut memory = crate::memory::get_upgrades_memory();
+    let mut writer = Writer::new(&mut memory, 0);
+    writer.write(&len.to_le_bytes()).unwrap();
+    writer.write(&state_bytes).unwrap();
+}
+
+// A post-upgrade hook for deserializing the data back into the heap.
+#[post_upgrade]
+fn post_upgrade() {
+    let memory = crate::memory::get_upgrades_memory();
+
+    // Read the length of the state bytes. Launchpads released before the
+    // registry existed never wrote any state, nor grew the upgrades memory.
+    let state_len = if memory.size() == 0 {
+        0
+    } else {
+        let mut state_len_bytes = [0; 4];
+        memory.read(0, &mut state_len_bytes);
+        u32::from_le_bytes(state_len_bytes) as usize
+    };
+    if state_len == 0 {
+        STATE.with(|s| s.borrow().certify_log_tip());
+        set_monitor_timer();
+        set_stats_timer();
+        return;
+    }
+
+    // Read the bytes
+    let mut state_bytes = vec![0; state_len];
+    memory.read(4, &mut state_bytes);
+
+    // Deserialize and set the state.
+    let state = ciborium::de::from_reader(&*state_bytes).expect("failed to decode state");
+    STATE.with(|s| *s.borrow_mut() = state);
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        s.normalize_symbol_reservations();
+        s.index_report_queue();
+    });
+    STATE.with(|s| s.borrow().certify_log_tip());
+
+    // Timers do not survive upgrades, resume an interrupted fleet upgrade.
+    if STATE.with(|s| s.borrow().is_upgrade_running()) {
+        set_upgrade_timer();
+    }
+    if STATE.with(|s| !s.borrow().running_mint_jobs().is_empty()) {
+        set_mint_timer();
+    }
+    set_monitor_timer();
+    set_stats_timer();
+}
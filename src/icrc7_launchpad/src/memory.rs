@@ -0,0 +1,127 @@
+use crate::{
+    state::MEMORY_MANAGER,
+    types::{
+        CanisterHealth, CollectionInfo, CollectionReport, CollectionStats, CollectionTemplate,
+        CollectionUpgrade, CyclesUsage, Deployment, FailedFeeTransfer, MintJob, ModerationEntry,
+        Reservation, WasmChunkKey, WasmVersion,
+    },
+};
+use candid::Principal;
+use ic_stable_structures::{
+    memory_manager::{MemoryId, VirtualMemory},
+    DefaultMemoryImpl, StableBTreeMap,
+};
+use icrc7_types::icrc3_types::Block;
+
+// A memory for upgrades, where data from the heap can be serialized/deserialized.
+const UPGRADES: MemoryId = MemoryId::new(0);
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+pub fn get_upgrades_memory() -> Memory {
+    MEMORY_MANAGER.with(|m| m.borrow().get(UPGRADES))
+}
+
+pub fn get_collections_memory() -> StableBTreeMap<Principal, CollectionInfo, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))))
+}
+
+pub fn get_creator_collections_memory() -> StableBTreeMap<(Principal, Principal), (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))))
+}
+
+pub fn get_wasm_versions_memory() -> StableBTreeMap<String, WasmVersion, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))))
+}
+
+pub fn get_wasm_chunks_memory() -> StableBTreeMap<WasmChunkKey, Vec<u8>, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))))
+}
+
+pub fn get_collection_upgrades_memory() -> StableBTreeMap<Principal, CollectionUpgrade, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))))
+}
+
+pub fn get_deployments_memory() -> StableBTreeMap<Principal, Deployment, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))))
+}
+
+pub fn get_spare_canisters_memory() -> StableBTreeMap<Principal, u64, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))))
+}
+
+pub fn get_cycles_usage_memory() -> StableBTreeMap<Principal, CyclesUsage, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))))
+}
+
+pub fn get_canister_health_memory() -> StableBTreeMap<Principal, CanisterHealth, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))))
+}
+
+pub fn get_collection_templates_memory() -> StableBTreeMap<String, CollectionTemplate, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))))
+}
+
+pub fn get_admins_memory() -> StableBTreeMap<Principal, u64, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))))
+}
+
+pub fn get_allowlist_memory() -> StableBTreeMap<Principal, u64, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))))
+}
+
+pub fn get_creator_deployments_memory() -> StableBTreeMap<(Principal, u64), (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))))
+}
+
+pub fn get_symbol_reservations_memory() -> StableBTreeMap<String, Reservation, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))))
+}
+
+pub fn get_name_reservations_memory() -> StableBTreeMap<String, Reservation, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))))
+}
+
+pub fn get_blocks_memory() -> StableBTreeMap<u128, Block, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))))
+}
+
+pub fn get_mint_manifests_memory() -> StableBTreeMap<(Principal, u64), Vec<u8>, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))))
+}
+
+pub fn get_mint_queue_memory() -> StableBTreeMap<(Principal, u64), Vec<u8>, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))))
+}
+
+pub fn get_mint_jobs_memory() -> StableBTreeMap<Principal, MintJob, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))))
+}
+
+pub fn get_collection_stats_memory() -> StableBTreeMap<Principal, CollectionStats, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))))
+}
+
+pub fn get_minting_consents_memory() -> StableBTreeMap<(Principal, Principal), u64, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))))
+}
+
+pub fn get_reports_memory() -> StableBTreeMap<u64, CollectionReport, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))))
+}
+
+pub fn get_open_reports_memory() -> StableBTreeMap<(Principal, Principal), u64, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))))
+}
+
+pub fn get_moderation_log_memory() -> StableBTreeMap<u64, ModerationEntry, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))))
+}
+
+pub fn get_failed_fee_transfers_memory() -> StableBTreeMap<u64, FailedFeeTransfer, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))))
+}
+
+pub fn get_report_queue_memory() -> StableBTreeMap<u64, (), Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))))
+}
@@ -0,0 +1,98 @@
+use std::{cell::Cell, time::Duration};
+
+use candid::Principal;
+use futures::future::join_all;
+use icrc7_types::{errors::MintError, icrc7_types::MintArg};
+use icrc_ledger_types::icrc1::account::Account;
+
+use crate::{state::STATE, types::MintJobStatus};
+
+// Number of mint calls sent to one collection per timer run.
+pub const MINT_BATCH_SIZE: usize = 50;
+
+// Failures kept on a mint job for the creator to inspect, later ones are only counted.
+pub const MINT_JOB_MAX_FAILURES: usize = 100;
+
+thread_local! {
+    // Guards against a new run starting while the previous one still awaits calls.
+    static MINT_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_mint_timer() {
+    ic_cdk_timers::set_timer(Duration::from_secs(1), || {
+        ic_cdk::spawn(mint_manifests_task());
+    });
+}
+
+async fn mint_manifests_task() {
+    if MINT_RUNNING.with(|r| r.replace(true)) {
+        return;
+    }
+    let jobs = STATE.with(|s| s.borrow().running_mint_jobs());
+    for job in jobs {
+        let batch = STATE.with(|s| s.borrow().next_mint_batch(job.canister_id, MINT_BATCH_SIZE));
+        if batch.is_empty() {
+            let minting_account = job.minting_account.unwrap_or(Account {
+                owner: job.creator,
+                subaccount: None,
+            });
+            let status = match hand_over_minting(job.canister_id, minting_account).await {
+                Ok(()) => MintJobStatus::Completed,
+                Err(message) => MintJobStatus::Failed { message },
+            };
+            STATE.with(|s| s.borrow_mut().set_mint_job_status(&job.canister_id, status));
+            continue;
+        }
+        mint_batch(job.canister_id, batch).await;
+    }
+    MINT_RUNNING.with(|r| r.set(false));
+
+    if STATE.with(|s| !s.borrow().running_mint_jobs().is_empty()) {
+        set_mint_timer();
+    }
+}
+
+async fn mint_batch(canister_id: Principal, batch: Vec<(u64, MintArg)>) {
+    let calls = batch.into_iter().map(|(index, mint)| async move {
+        let token_id = mint.token_id;
+        let result: Result<(Result<u128, MintError>,), _> =
+            ic_cdk::call(canister_id, "mint", (mint,)).await;
+        (index, token_id, result)
+    });
+    for (index, token_id, result) in join_all(calls).await {
+        match result {
+            Ok((result,)) => {
+                let result = result.map(|_| ()).map_err(|e| format!("{:?}", e));
+                STATE.with(|s| {
+                    s.borrow_mut()
+                        .record_mint(canister_id, index, token_id, result)
+                });
+            }
+            // The token stays queued, the creator resumes the job once the
+            // collection is reachable again.
+            Err((code, message)) => {
+                let message = format!("Minting token {} failed: {:?} {}", token_id, code, message);
+                STATE.with(|s| {
+                    s.borrow_mut()
+                        .set_mint_job_status(&canister_id, MintJobStatus::Failed { message })
+                });
+            }
+        }
+    }
+}
+
+// Hands the minting authority over once the launchpad is done minting.
+async fn hand_over_minting(canister_id: Principal, account: Account) -> Result<(), String> {
+    let (updated,): (bool,) = ic_cdk::call(canister_id, "set_minting_authority", (account,))
+        .await
+        .map_err(|(code, message)| {
+            format!(
+                "Failed to hand over minting of {}: {:?} {}",
+                canister_id, code, message
+            )
+        })?;
+    if !updated {
+        return Err(format!("{} refused the new minting authority", canister_id));
+    }
+    Ok(())
+}
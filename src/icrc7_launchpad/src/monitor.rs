@@ -0,0 +1,143 @@
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+
+use candid::{Nat, Principal};
+use ic_cdk_timers::TimerId;
+use icrc7_types::icrc3_types::{GetArchiveArgs, GetArchivesResultItem};
+
+use crate::{
+    deploy::top_up_collection_canister,
+    state::STATE,
+    types::{CanisterHealth, CanisterKind, CollectionInfo},
+};
+
+pub const MONITOR_DEFAULT_INTERVAL_SECS: u64 = 6 * 60 * 60;
+pub const MONITOR_DEFAULT_THRESHOLD_CYCLES: u128 = 2_000_000_000_000;
+pub const MONITOR_DEFAULT_TOP_UP_CYCLES: u128 = 5_000_000_000_000;
+
+thread_local! {
+    static MONITOR_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+    // Guards against a new run starting while the previous one still awaits calls.
+    static MONITOR_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// (Re)arms the periodic cycles check according to the current monitor config.
+pub fn set_monitor_timer() {
+    if let Some(timer_id) = MONITOR_TIMER.with(|t| t.borrow_mut().take()) {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+    let config = STATE.with(|s| s.borrow().monitor_config());
+    if !config.enabled {
+        return;
+    }
+    let timer_id =
+        ic_cdk_timers::set_timer_interval(Duration::from_secs(config.interval_secs), || {
+            ic_cdk::spawn(monitor_cycles_task());
+        });
+    MONITOR_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
+}
+
+async fn monitor_cycles_task() {
+    if MONITOR_RUNNING.with(|r| r.replace(true)) {
+        return;
+    }
+    let mut cursor = None;
+    while let Some(collection) = STATE.with(|s| s.borrow().next_collection(cursor)) {
+        cursor = Some(collection.canister_id);
+        check_canister(
+            collection.canister_id,
+            &collection,
+            CanisterKind::Collection,
+        )
+        .await;
+
+        match archives_of(collection.canister_id).await {
+            Ok(archives) => {
+                // The collection lists itself first as the holder of the latest blocks.
+                for archive in archives
+                    .into_iter()
+                    .filter(|archive| archive.canister_id != collection.canister_id)
+                {
+                    check_canister(archive.canister_id, &collection, CanisterKind::Archive).await;
+                }
+            }
+            Err(e) => {
+                ic_cdk::println!(
+                    "monitor_cycles_task: cannot list archives of {}: {}",
+                    collection.canister_id,
+                    e
+                );
+            }
+        }
+    }
+    MONITOR_RUNNING.with(|r| r.set(false));
+}
+
+pub async fn wallet_balance(canister_id: Principal) -> Result<u128, String> {
+    let call_result: Result<(Nat,), _> = ic_cdk::call(canister_id, "wallet_balance", ()).await;
+    match call_result {
+        Ok((balance,)) => Ok(u128::try_from(balance.0).unwrap_or(u128::MAX)),
+        Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+pub async fn archives_of(canister_id: Principal) -> Result<Vec<GetArchivesResultItem>, String> {
+    let call_result: Result<(Vec<GetArchivesResultItem>,), _> = ic_cdk::call(
+        canister_id,
+        "icrc3_get_archives",
+        (GetArchiveArgs { from: None },),
+    )
+    .await;
+    match call_result {
+        Ok((archives,)) => Ok(archives),
+        Err((code, msg)) => Err(format!("Rejection Code: {:?}, Message: {:?}", code, msg)),
+    }
+}
+
+/// Reads the balance of `canister_id` and tops it up from the launchpad when
+/// it is below the configured threshold. Top-ups are billed to the creator
+/// of `collection`.
+async fn check_canister(canister_id: Principal, collection: &CollectionInfo, kind: CanisterKind) {
+    let config = STATE.with(|s| s.borrow().monitor_config());
+    let mut health = CanisterHealth {
+        canister_id,
+        collection_id: collection.canister_id,
+        kind,
+        balance: None,
+        topped_up: 0,
+        error: None,
+        checked_at: 0,
+    };
+
+    match wallet_balance(canister_id).await {
+        Err(e) => health.error = Some(e),
+        Ok(balance) => {
+            health.balance = Some(balance);
+            if balance < config.threshold_cycles && config.top_up_cycles > 0 {
+                if ic_cdk::api::canister_balance128() < config.top_up_cycles {
+                    health.error = Some("Insufficient launchpad cycles balance".into());
+                } else {
+                    match top_up_collection_canister(canister_id, config.top_up_cycles).await {
+                        Ok(()) => {
+                            health.balance = Some(balance.saturating_add(config.top_up_cycles));
+                            health.topped_up = config.top_up_cycles;
+                            STATE.with(|s| {
+                                s.borrow_mut().record_top_up_cycles(
+                                    collection.creator,
+                                    canister_id,
+                                    config.top_up_cycles,
+                                )
+                            });
+                        }
+                        Err(e) => health.error = Some(e),
+                    }
+                }
+            }
+        }
+    }
+
+    health.checked_at = ic_cdk::api::time();
+    STATE.with(|s| s.borrow_mut().record_canister_health(health));
+}
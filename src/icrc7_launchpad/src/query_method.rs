@@ -0,0 +1,250 @@
+use candid::Principal;
+use ic_cdk_macros::query;
+
+use crate::{
+    guards::admin_guard,
+    state::STATE,
+    types::{
+        Arg, ArgValidationError, CanisterHealth, CollectionFilter, CollectionInfo,
+        CollectionReport, CollectionSearchPage, CollectionStats, CollectionTemplate,
+        CollectionUpgrade, CyclesConfig, CyclesUsage, Deployment, DeploymentFee, DeploymentQuota,
+        FailedFeeTransfer, LaunchpadStats, MintJob, ModerationEntry, MonitorConfig, Reservation,
+        ReservationConfig, SettingsLimits, UpgradeJob, WasmVersion,
+    },
+    validation::validate_arg,
+};
+
+#[query]
+pub fn get_collection(canister_id: Principal) -> Option<CollectionInfo> {
+    STATE.with(|s| s.borrow().get_collection(&canister_id))
+}
+
+#[query]
+pub fn list_collections(prev: Option<Principal>, take: Option<u64>) -> Vec<CollectionInfo> {
+    STATE.with(|s| s.borrow().list_collections(prev, take))
+}
+
+#[query]
+pub fn collections_of(
+    creator: Principal,
+    prev: Option<Principal>,
+    take: Option<u64>,
+) -> Vec<CollectionInfo> {
+    STATE.with(|s| s.borrow().collections_of(creator, prev, take))
+}
+
+#[query]
+pub fn total_collections() -> u64 {
+    STATE.with(|s| s.borrow().total_collections())
+}
+
+#[query]
+pub fn deployment_fee() -> Option<DeploymentFee> {
+    STATE.with(|s| s.borrow().deployment_fee())
+}
+
+#[query(guard = "admin_guard")]
+pub fn failed_fee_transfers(prev: Option<u64>, take: Option<u64>) -> Vec<FailedFeeTransfer> {
+    STATE.with(|s| s.borrow().failed_fee_transfers(prev, take))
+}
+
+#[query]
+pub fn wasm_versions() -> Vec<WasmVersion> {
+    STATE.with(|s| s.borrow().wasm_versions())
+}
+
+#[query]
+pub fn default_wasm_version() -> Option<String> {
+    STATE.with(|s| s.borrow().default_wasm_version())
+}
+
+#[query]
+pub fn upgrade_job() -> Option<UpgradeJob> {
+    STATE.with(|s| s.borrow().upgrade_job())
+}
+
+#[query]
+pub fn get_collection_upgrade(canister_id: Principal) -> Option<CollectionUpgrade> {
+    STATE.with(|s| s.borrow().get_collection_upgrade(&canister_id))
+}
+
+#[query]
+pub fn collection_upgrades(prev: Option<Principal>, take: Option<u64>) -> Vec<CollectionUpgrade> {
+    STATE.with(|s| s.borrow().collection_upgrades(prev, take))
+}
+
+#[query]
+pub fn get_deployment(canister_id: Principal) -> Option<Deployment> {
+    STATE.with(|s| s.borrow().get_deployment(&canister_id))
+}
+
+// Admins see every unfinished deployment, creators only their own.
+#[query]
+pub fn pending_deployments(prev: Option<Principal>, take: Option<u64>) -> Vec<Deployment> {
+    let creator = match admin_guard() {
+        Ok(()) => None,
+        Err(_) => Some(ic_cdk::caller()),
+    };
+    STATE.with(|s| s.borrow().pending_deployments(creator, prev, take))
+}
+
+#[query(guard = "admin_guard")]
+pub fn spare_canisters() -> Vec<Principal> {
+    STATE.with(|s| s.borrow().spare_canisters())
+}
+
+#[query]
+pub fn cycles_config() -> CyclesConfig {
+    STATE.with(|s| s.borrow().cycles_config())
+}
+
+#[query]
+pub fn cycles_usage_of(creator: Principal) -> CyclesUsage {
+    STATE.with(|s| s.borrow().cycles_usage_of(&creator))
+}
+
+#[query]
+pub fn monitor_config() -> MonitorConfig {
+    STATE.with(|s| s.borrow().monitor_config())
+}
+
+#[query]
+pub fn get_canister_health(canister_id: Principal) -> Option<CanisterHealth> {
+    STATE.with(|s| s.borrow().get_canister_health(&canister_id))
+}
+
+#[query]
+pub fn cycles_health_report(prev: Option<Principal>, take: Option<u64>) -> Vec<CanisterHealth> {
+    STATE.with(|s| s.borrow().at_risk_canisters(prev, take))
+}
+
+#[query]
+pub fn get_collection_template(name: String) -> Option<CollectionTemplate> {
+    STATE.with(|s| s.borrow().get_collection_template(&name))
+}
+
+#[query]
+pub fn collection_templates() -> Vec<CollectionTemplate> {
+    STATE.with(|s| s.borrow().collection_templates())
+}
+
+/// Dry-runs the checks `mint_collection_canister` applies before spending any cycles.
+#[query]
+pub fn validate_collection_arg(arg: Arg) -> Vec<ArgValidationError> {
+    validate_arg(&arg)
+}
+
+#[query]
+pub fn settings_limits() -> SettingsLimits {
+    STATE.with(|s| s.borrow().settings_limits())
+}
+
+#[query]
+pub fn admins() -> Vec<Principal> {
+    STATE.with(|s| s.borrow().admins())
+}
+
+#[query]
+pub fn is_admin(principal: Principal) -> bool {
+    crate::guards::is_admin(&principal)
+}
+
+#[query]
+pub fn deployments_paused() -> bool {
+    STATE.with(|s| s.borrow().deployments_paused)
+}
+
+#[query]
+pub fn allowlist_enabled() -> bool {
+    STATE.with(|s| s.borrow().allowlist_enabled)
+}
+
+#[query]
+pub fn is_allowlisted(principal: Principal) -> bool {
+    STATE.with(|s| s.borrow().is_allowlisted(&principal))
+}
+
+#[query(guard = "admin_guard")]
+pub fn allowlist(prev: Option<Principal>, take: Option<u64>) -> Vec<Principal> {
+    STATE.with(|s| s.borrow().allowlist(prev, take))
+}
+
+#[query]
+pub fn deployment_quota() -> Option<DeploymentQuota> {
+    STATE.with(|s| s.borrow().deployment_quota.clone())
+}
+
+#[query]
+pub fn deployments_in_window(creator: Principal) -> u32 {
+    STATE.with(|s| s.borrow().deployments_in_window(creator))
+}
+
+#[query]
+pub fn reservation_config() -> ReservationConfig {
+    STATE.with(|s| s.borrow().reservation_config.clone())
+}
+
+#[query]
+pub fn get_symbol_reservation(symbol: String) -> Option<Reservation> {
+    STATE.with(|s| s.borrow().get_symbol_reservation(&symbol))
+}
+
+#[query]
+pub fn get_name_reservation(name: String) -> Option<Reservation> {
+    STATE.with(|s| s.borrow().get_name_reservation(&name))
+}
+
+#[query]
+pub fn search_collections(
+    filter: CollectionFilter,
+    prev: Option<Principal>,
+    take: Option<u64>,
+) -> CollectionSearchPage {
+    STATE.with(|s| s.borrow().search_collections(filter, prev, take))
+}
+
+#[query]
+pub fn get_mint_job(canister_id: Principal) -> Option<MintJob> {
+    STATE.with(|s| s.borrow().get_mint_job(&canister_id))
+}
+
+#[query]
+pub fn mint_manifest_len() -> u64 {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow().mint_manifest_len(caller))
+}
+
+#[query]
+pub fn launchpad_stats() -> Option<LaunchpadStats> {
+    STATE.with(|s| s.borrow().launchpad_stats.clone())
+}
+
+#[query]
+pub fn get_collection_stats(canister_id: Principal) -> Option<CollectionStats> {
+    STATE.with(|s| s.borrow().get_collection_stats(&canister_id))
+}
+
+#[query]
+pub fn list_collection_stats(prev: Option<Principal>, take: Option<u64>) -> Vec<CollectionStats> {
+    STATE.with(|s| s.borrow().list_collection_stats(prev, take))
+}
+
+#[query]
+pub fn has_minting_consent(owner: Principal, deployer: Principal) -> bool {
+    STATE.with(|s| s.borrow().has_minting_consent(owner, deployer))
+}
+
+#[query(guard = "admin_guard")]
+pub fn review_queue(prev: Option<u64>, take: Option<u64>) -> Vec<CollectionReport> {
+    STATE.with(|s| s.borrow().review_queue(prev, take))
+}
+
+#[query(guard = "admin_guard")]
+pub fn get_report(report_id: u64) -> Option<CollectionReport> {
+    STATE.with(|s| s.borrow().get_report(report_id))
+}
+
+#[query]
+pub fn moderation_log(prev: Option<u64>, take: Option<u64>) -> Vec<ModerationEntry> {
+    STATE.with(|s| s.borrow().moderation_log(prev, take))
+}
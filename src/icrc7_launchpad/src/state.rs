@@ -0,0 +1,1928 @@
+use std::{cell::RefCell, ops::Bound};
+
+use crate::{
+    deploy::{
+        SETTINGS_DEFAULT_COMPUTE_ALLOCATION, SETTINGS_DEFAULT_FREEZING_THRESHOLD,
+        SETTINGS_DEFAULT_MEMORY_ALLOCATION, SETTINGS_DEFAULT_RESERVED_CYCLES_LIMIT,
+    },
+    icrc3::{
+        certify_tip, principal_value, tx, BTYPE_CONTROL, BTYPE_DEPLOY, BTYPE_TOP_UP, BTYPE_UPGRADE,
+    },
+    memory::{
+        get_admins_memory, get_allowlist_memory, get_blocks_memory, get_canister_health_memory,
+        get_collection_stats_memory, get_collection_templates_memory,
+        get_collection_upgrades_memory, get_collections_memory, get_creator_collections_memory,
+        get_creator_deployments_memory, get_cycles_usage_memory, get_deployments_memory,
+        get_failed_fee_transfers_memory, get_mint_jobs_memory, get_mint_manifests_memory,
+        get_mint_queue_memory, get_minting_consents_memory, get_moderation_log_memory,
+        get_name_reservations_memory, get_open_reports_memory, get_report_queue_memory,
+        get_reports_memory, get_spare_canisters_memory, get_symbol_reservations_memory,
+        get_wasm_chunks_memory, get_wasm_versions_memory, Memory,
+    },
+    mint::MINT_JOB_MAX_FAILURES,
+    types::{
+        ArgOverrides, CanisterHealth, CollectionFilter, CollectionInfo, CollectionMetadata,
+        CollectionReport, CollectionSearchPage, CollectionSettings, CollectionStats,
+        CollectionTemplate, CollectionUpgrade, ControlStatus, CyclesConfig, CyclesUsage, Delisting,
+        Deployment, DeploymentFee, DeploymentQuota, DeploymentStatus, FailedFeeTransfer,
+        FeeTransferKind, InitArg, LaunchpadStats, MintFailure, MintJob, MintJobStatus,
+        ModerationAction, ModerationEntry, MonitorConfig, ReportStatus, Reservation,
+        ReservationConfig, SettingsLimits, UpgradeJob, UpgradeStatus, WasmChunkKey, WasmVersion,
+    },
+    validation::MAX_MINT_MANIFEST_LEN,
+};
+use candid::{Decode, Encode, Principal};
+use ic_stable_structures::{memory_manager::MemoryManager, DefaultMemoryImpl, StableBTreeMap};
+use icrc7_types::icrc3_types::{Block, GetBlocksArgs, GetBlocksResult, QueryBlock};
+use icrc7_types::icrc7_types::MintArg;
+use icrc_ledger_types::{
+    icrc::generic_value::{Map, Value},
+    icrc1::account::Account,
+};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+#[derive(Serialize, Deserialize)]
+pub struct State {
+    #[serde(skip, default = "get_collections_memory")]
+    pub collections: StableBTreeMap<Principal, CollectionInfo, Memory>,
+    #[serde(skip, default = "get_creator_collections_memory")]
+    pub creator_collections: StableBTreeMap<(Principal, Principal), (), Memory>,
+    pub deployment_fee: Option<DeploymentFee>,
+    #[serde(skip, default = "get_wasm_versions_memory")]
+    pub wasm_versions: StableBTreeMap<String, WasmVersion, Memory>,
+    #[serde(skip, default = "get_wasm_chunks_memory")]
+    pub wasm_chunks: StableBTreeMap<WasmChunkKey, Vec<u8>, Memory>,
+    pub default_wasm_version: Option<String>,
+    #[serde(skip, default = "get_collection_upgrades_memory")]
+    pub collection_upgrades: StableBTreeMap<Principal, CollectionUpgrade, Memory>,
+    pub upgrade_job: Option<UpgradeJob>,
+    #[serde(skip, default = "get_deployments_memory")]
+    pub deployments: StableBTreeMap<Principal, Deployment, Memory>,
+    #[serde(skip, default = "get_spare_canisters_memory")]
+    pub spare_canisters: StableBTreeMap<Principal, u64, Memory>,
+    #[serde(default)]
+    pub cycles_config: CyclesConfig,
+    #[serde(skip, default = "get_cycles_usage_memory")]
+    pub cycles_usage: StableBTreeMap<Principal, CyclesUsage, Memory>,
+    #[serde(default)]
+    pub monitor_config: MonitorConfig,
+    #[serde(skip, default = "get_canister_health_memory")]
+    pub canister_health: StableBTreeMap<Principal, CanisterHealth, Memory>,
+    #[serde(skip, default = "get_collection_templates_memory")]
+    pub collection_templates: StableBTreeMap<String, CollectionTemplate, Memory>,
+    #[serde(default)]
+    pub settings_limits: SettingsLimits,
+    #[serde(skip, default = "get_admins_memory")]
+    pub admins: StableBTreeMap<Principal, u64, Memory>,
+    #[serde(default)]
+    pub allowlist_enabled: bool,
+    #[serde(skip, default = "get_allowlist_memory")]
+    pub allowlist: StableBTreeMap<Principal, u64, Memory>,
+    pub deployment_quota: Option<DeploymentQuota>,
+    #[serde(skip, default = "get_creator_deployments_memory")]
+    pub creator_deployments: StableBTreeMap<(Principal, u64), (), Memory>,
+    #[serde(default)]
+    pub deployments_paused: bool,
+    #[serde(default)]
+    pub reservation_config: ReservationConfig,
+    #[serde(skip, default = "get_symbol_reservations_memory")]
+    pub symbol_reservations: StableBTreeMap<String, Reservation, Memory>,
+    #[serde(skip, default = "get_name_reservations_memory")]
+    pub name_reservations: StableBTreeMap<String, Reservation, Memory>,
+    #[serde(skip, default = "get_blocks_memory")]
+    pub blocks: StableBTreeMap<u128, Block, Memory>,
+    #[serde(skip, default = "get_mint_manifests_memory")]
+    pub mint_manifests: StableBTreeMap<(Principal, u64), Vec<u8>, Memory>,
+    #[serde(skip, default = "get_mint_queue_memory")]
+    pub mint_queue: StableBTreeMap<(Principal, u64), Vec<u8>, Memory>,
+    #[serde(skip, default = "get_mint_jobs_memory")]
+    pub mint_jobs: StableBTreeMap<Principal, MintJob, Memory>,
+    #[serde(skip, default = "get_collection_stats_memory")]
+    pub collection_stats: StableBTreeMap<Principal, CollectionStats, Memory>,
+    #[serde(skip, default = "get_minting_consents_memory")]
+    pub minting_consents: StableBTreeMap<(Principal, Principal), u64, Memory>,
+    #[serde(skip, default = "get_reports_memory")]
+    pub reports: StableBTreeMap<u64, CollectionReport, Memory>,
+    #[serde(skip, default = "get_open_reports_memory")]
+    pub open_reports: StableBTreeMap<(Principal, Principal), u64, Memory>,
+    #[serde(skip, default = "get_moderation_log_memory")]
+    pub moderation_log: StableBTreeMap<u64, ModerationEntry, Memory>,
+    #[serde(default)]
+    pub delisted_collections: u64,
+    pub launchpad_stats: Option<LaunchpadStats>,
+    #[serde(skip, default = "get_failed_fee_transfers_memory")]
+    pub failed_fee_transfers: StableBTreeMap<u64, FailedFeeTransfer, Memory>,
+    // Ids are not reused, a transfer being retried is out of the map meanwhile.
+    #[serde(default)]
+    pub next_fee_transfer_id: u64,
+    // The ids of the open reports, the review queue pages over it.
+    #[serde(skip, default = "get_report_queue_memory")]
+    pub report_queue: StableBTreeMap<u64, (), Memory>,
+}
+
+pub const RESERVATION_DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+fn controllers_value(control: &ControlStatus) -> Value {
+    let controllers = match control {
+        ControlStatus::Managed { controllers } | ControlStatus::Relinquished { controllers } => {
+            controllers.clone()
+        }
+        ControlStatus::Blackholed => vec![],
+    };
+    Value::Array(controllers.into_iter().map(principal_value).collect())
+}
+
+// Names are unique regardless of case and surrounding whitespace.
+fn name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+// Symbols likewise, keyed in their usual upper case.
+fn symbol_key(symbol: &str) -> String {
+    symbol.trim().to_uppercase()
+}
+
+fn live_reservation(
+    reservations: &StableBTreeMap<String, Reservation, Memory>,
+    key: &String,
+    now: u64,
+) -> Option<Reservation> {
+    reservations.get(key).filter(|reservation| {
+        reservation
+            .expires_at
+            .is_none_or(|expires_at| expires_at > now)
+    })
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            collections: get_collections_memory(),
+            creator_collections: get_creator_collections_memory(),
+            deployment_fee: None,
+            wasm_versions: get_wasm_versions_memory(),
+            wasm_chunks: get_wasm_chunks_memory(),
+            default_wasm_version: None,
+            collection_upgrades: get_collection_upgrades_memory(),
+            upgrade_job: None,
+            deployments: get_deployments_memory(),
+            spare_canisters: get_spare_canisters_memory(),
+            cycles_config: CyclesConfig::default(),
+            cycles_usage: get_cycles_usage_memory(),
+            monitor_config: MonitorConfig::default(),
+            canister_health: get_canister_health_memory(),
+            collection_templates: get_collection_templates_memory(),
+            settings_limits: SettingsLimits::default(),
+            admins: get_admins_memory(),
+            allowlist_enabled: false,
+            allowlist: get_allowlist_memory(),
+            deployment_quota: None,
+            creator_deployments: get_creator_deployments_memory(),
+            deployments_paused: false,
+            reservation_config: ReservationConfig::default(),
+            symbol_reservations: get_symbol_reservations_memory(),
+            name_reservations: get_name_reservations_memory(),
+            blocks: get_blocks_memory(),
+            mint_manifests: get_mint_manifests_memory(),
+            mint_queue: get_mint_queue_memory(),
+            mint_jobs: get_mint_jobs_memory(),
+            collection_stats: get_collection_stats_memory(),
+            minting_consents: get_minting_consents_memory(),
+            reports: get_reports_memory(),
+            open_reports: get_open_reports_memory(),
+            moderation_log: get_moderation_log_memory(),
+            delisted_collections: 0,
+            launchpad_stats: None,
+            failed_fee_transfers: get_failed_fee_transfers_memory(),
+            next_fee_transfer_id: 0,
+            report_queue: get_report_queue_memory(),
+        }
+    }
+}
+
+impl State {
+    pub const DEFAULT_TAKE_VALUE: u64 = 32;
+    pub const DEFAULT_MAX_TAKE_VALUE: u64 = 100;
+    pub const MAX_TEMPLATE_NAME_LEN: usize = 64;
+    pub const MAX_REPORT_REASON_LEN: usize = 1024;
+    pub const MAX_OPEN_REPORTS_PER_COLLECTION: usize = 50;
+    pub const MAX_SEARCH_SCAN: usize = 1_000;
+
+    fn get_current_take(&self, take: Option<u64>) -> usize {
+        take.unwrap_or(State::DEFAULT_TAKE_VALUE)
+            .min(State::DEFAULT_MAX_TAKE_VALUE) as usize
+    }
+
+    pub fn deployment_fee(&self) -> Option<DeploymentFee> {
+        self.deployment_fee.clone()
+    }
+
+    pub fn record_failed_fee_transfer(
+        &mut self,
+        kind: FeeTransferKind,
+        fee: DeploymentFee,
+        to: Account,
+        error: String,
+    ) -> u64 {
+        let id = self.next_fee_transfer_id;
+        self.next_fee_transfer_id += 1;
+        let now = ic_cdk::api::time();
+        self.failed_fee_transfers.insert(
+            id,
+            FailedFeeTransfer {
+                id,
+                kind,
+                fee,
+                to,
+                error,
+                attempts: 1,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        id
+    }
+
+    /// Removes a failed transfer while it is retried, so that concurrent
+    /// retries cannot pay it twice. A retry that fails puts it back.
+    pub fn take_failed_fee_transfer(&mut self, id: u64) -> Option<FailedFeeTransfer> {
+        self.failed_fee_transfers.remove(&id)
+    }
+
+    pub fn restore_failed_fee_transfer(&mut self, mut transfer: FailedFeeTransfer, error: String) {
+        transfer.error = error;
+        transfer.attempts += 1;
+        transfer.updated_at = ic_cdk::api::time();
+        self.failed_fee_transfers.insert(transfer.id, transfer);
+    }
+
+    pub fn failed_fee_transfers(
+        &self,
+        prev: Option<u64>,
+        take: Option<u64>,
+    ) -> Vec<FailedFeeTransfer> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.failed_fee_transfers
+            .range((start, Bound::Unbounded))
+            .map(|(_, transfer)| transfer)
+            .take(take)
+            .collect()
+    }
+
+    fn wasm_chunk_keys(&self, version: &str) -> Vec<WasmChunkKey> {
+        let start = WasmChunkKey {
+            version: version.to_string(),
+            index: 0,
+        };
+        self.wasm_chunks
+            .range(start..)
+            .take_while(|(key, _)| key.version == version)
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    pub fn upload_wasm_chunk(&mut self, version: String, chunk: Vec<u8>) -> Result<u32, String> {
+        if version.is_empty() {
+            return Err("Empty WASM version".into());
+        }
+        let mut wasm_version = self
+            .wasm_versions
+            .get(&version)
+            .unwrap_or_else(|| WasmVersion {
+                version: version.clone(),
+                size: 0,
+                chunk_count: 0,
+                sha256: None,
+                committed: false,
+                uploaded_at: ic_cdk::api::time(),
+            });
+        if wasm_version.committed {
+            return Err(format!("WASM version {} is already committed", version));
+        }
+        let index = wasm_version.chunk_count;
+        wasm_version.chunk_count += 1;
+        wasm_version.size += chunk.len() as u64;
+        self.wasm_chunks.insert(
+            WasmChunkKey {
+                version: version.clone(),
+                index,
+            },
+            chunk,
+        );
+        self.wasm_versions.insert(version, wasm_version);
+        Ok(index)
+    }
+
+    pub fn commit_wasm_version(
+        &mut self,
+        version: String,
+        expected_sha256: Option<ByteBuf>,
+    ) -> Result<WasmVersion, String> {
+        let mut wasm_version = self
+            .wasm_versions
+            .get(&version)
+            .ok_or_else(|| format!("Unknown WASM version {}", version))?;
+        if wasm_version.committed {
+            return Err(format!("WASM version {} is already committed", version));
+        }
+        let mut hasher = Sha256::new();
+        for key in self.wasm_chunk_keys(&version) {
+            hasher.update(self.wasm_chunks.get(&key).unwrap());
+        }
+        let sha256 = ByteBuf::from(hasher.finalize().to_vec());
+        if let Some(expected_sha256) = expected_sha256 {
+            if expected_sha256 != sha256 {
+                return Err(format!("SHA-256 mismatch for WASM version {}", version));
+            }
+        }
+        wasm_version.sha256 = Some(sha256);
+        wasm_version.committed = true;
+        self.wasm_versions
+            .insert(version.clone(), wasm_version.clone());
+        if self.default_wasm_version.is_none() {
+            self.default_wasm_version = Some(version);
+        }
+        Ok(wasm_version)
+    }
+
+    pub fn set_default_wasm_version(&mut self, version: String) -> Result<(), String> {
+        match self.wasm_versions.get(&version) {
+            Some(wasm_version) if wasm_version.committed => {
+                self.default_wasm_version = Some(version);
+                Ok(())
+            }
+            Some(_) => Err(format!("WASM version {} is not committed", version)),
+            None => Err(format!("Unknown WASM version {}", version)),
+        }
+    }
+
+    pub fn delete_wasm_version(&mut self, version: String) -> Result<(), String> {
+        if self.default_wasm_version.as_ref() == Some(&version) {
+            return Err("Cannot delete the default WASM version".into());
+        }
+        if self
+            .upgrade_job
+            .as_ref()
+            .is_some_and(|job| job.finished_at.is_none() && job.wasm_version == version)
+        {
+            return Err("Cannot delete the WASM version of the running upgrade job".into());
+        }
+        // `retry_deployment` reinstalls the version a failed deployment used.
+        if self.deployments.iter().any(|(_, deployment)| {
+            matches!(deployment.status, DeploymentStatus::Failed { .. })
+                && deployment.wasm_version == version
+        }) {
+            return Err("Cannot delete a WASM version that failed deployments can retry".into());
+        }
+        if self.wasm_versions.remove(&version).is_none() {
+            return Err(format!("Unknown WASM version {}", version));
+        }
+        for key in self.wasm_chunk_keys(&version) {
+            self.wasm_chunks.remove(&key);
+        }
+        Ok(())
+    }
+
+    pub fn wasm_versions(&self) -> Vec<WasmVersion> {
+        self.wasm_versions.iter().map(|(_, v)| v).collect()
+    }
+
+    pub fn default_wasm_version(&self) -> Option<String> {
+        self.default_wasm_version.clone()
+    }
+
+    /// Resolves `version`, or the default version, to a committed WASM version.
+    pub fn resolve_wasm_version(&self, version: Option<String>) -> Result<WasmVersion, String> {
+        let version = version
+            .or_else(|| self.default_wasm_version())
+            .ok_or_else(|| String::from("No default WASM version"))?;
+        match self.wasm_versions.get(&version) {
+            Some(wasm_version) if wasm_version.committed => Ok(wasm_version),
+            Some(_) => Err(format!("WASM version {} is not committed", version)),
+            None => Err(format!("Unknown WASM version {}", version)),
+        }
+    }
+
+    pub fn wasm_module(&self, version: Option<String>) -> Result<(String, Vec<u8>), String> {
+        let wasm_version = self.resolve_wasm_version(version)?;
+        let mut wasm_module = Vec::with_capacity(wasm_version.size as usize);
+        for key in self.wasm_chunk_keys(&wasm_version.version) {
+            wasm_module.extend(self.wasm_chunks.get(&key).unwrap());
+        }
+        Ok((wasm_version.version, wasm_module))
+    }
+
+    pub fn add_collection(&mut self, info: CollectionInfo) {
+        self.creator_collections
+            .insert((info.creator, info.canister_id), ());
+        self.collections.insert(info.canister_id, info);
+    }
+
+    pub fn set_collection_control(&mut self, canister_id: &Principal, control: ControlStatus) {
+        if let Some(mut info) = self.collections.get(canister_id) {
+            self.append_block(
+                BTYPE_CONTROL,
+                tx([
+                    ("canister_id", principal_value(*canister_id)),
+                    ("controllers", controllers_value(&control)),
+                ]),
+            );
+            info.control = Some(control);
+            self.collections.insert(*canister_id, info);
+        }
+    }
+
+    pub fn settings_limits(&self) -> SettingsLimits {
+        self.settings_limits.clone()
+    }
+
+    pub fn set_settings_limits(&mut self, limits: SettingsLimits) -> Result<(), String> {
+        if limits.max_compute_allocation > 100 {
+            return Err("Compute allocation cannot exceed 100".into());
+        }
+        self.settings_limits = limits;
+        Ok(())
+    }
+
+    /// Fills the unset fields of `settings` with the system defaults and
+    /// checks the result against the admin-set ceilings.
+    pub fn resolve_collection_settings(
+        &self,
+        settings: Option<CollectionSettings>,
+    ) -> Result<CollectionSettings, String> {
+        let settings = settings.unwrap_or_default();
+        let limits = &self.settings_limits;
+        let compute_allocation = settings
+            .compute_allocation
+            .unwrap_or(SETTINGS_DEFAULT_COMPUTE_ALLOCATION);
+        if compute_allocation > limits.max_compute_allocation {
+            return Err(format!(
+                "Compute allocation must be at most {}",
+                limits.max_compute_allocation
+            ));
+        }
+        let memory_allocation = settings
+            .memory_allocation
+            .unwrap_or(SETTINGS_DEFAULT_MEMORY_ALLOCATION);
+        if memory_allocation > limits.max_memory_allocation {
+            return Err(format!(
+                "Memory allocation must be at most {}",
+                limits.max_memory_allocation
+            ));
+        }
+        let freezing_threshold = settings
+            .freezing_threshold
+            .unwrap_or(SETTINGS_DEFAULT_FREEZING_THRESHOLD);
+        if freezing_threshold > limits.max_freezing_threshold {
+            return Err(format!(
+                "Freezing threshold must be at most {}",
+                limits.max_freezing_threshold
+            ));
+        }
+        let reserved_cycles_limit = settings
+            .reserved_cycles_limit
+            .unwrap_or(SETTINGS_DEFAULT_RESERVED_CYCLES_LIMIT);
+        if reserved_cycles_limit > limits.max_reserved_cycles_limit {
+            return Err(format!(
+                "Reserved cycles limit must be at most {}",
+                limits.max_reserved_cycles_limit
+            ));
+        }
+        Ok(CollectionSettings {
+            compute_allocation: Some(compute_allocation),
+            memory_allocation: Some(memory_allocation),
+            freezing_threshold: Some(freezing_threshold),
+            reserved_cycles_limit: Some(reserved_cycles_limit),
+        })
+    }
+
+    pub fn cycles_config(&self) -> CyclesConfig {
+        self.cycles_config.clone()
+    }
+
+    pub fn set_cycles_config(&mut self, config: CyclesConfig) -> Result<(), String> {
+        if config.min_cycles > config.default_cycles || config.default_cycles > config.max_cycles {
+            return Err("Cycles bounds must satisfy min <= default <= max".into());
+        }
+        if config.max_top_up_cycles > config.max_creator_top_up_cycles {
+            return Err("A single top-up cannot exceed the creator top-up budget".into());
+        }
+        self.cycles_config = config;
+        Ok(())
+    }
+
+    /// Resolves the cycles requested for a new collection against the configured bounds.
+    pub fn deploy_cycles(&self, cycles: Option<u128>) -> Result<u128, String> {
+        let cycles = cycles.unwrap_or(self.cycles_config.default_cycles);
+        if cycles < self.cycles_config.min_cycles || cycles > self.cycles_config.max_cycles {
+            return Err(format!(
+                "Cycles must be between {} and {}",
+                self.cycles_config.min_cycles, self.cycles_config.max_cycles
+            ));
+        }
+        Ok(cycles)
+    }
+
+    pub fn cycles_usage_of(&self, creator: &Principal) -> CyclesUsage {
+        self.cycles_usage.get(creator).unwrap_or_default()
+    }
+
+    pub fn record_deploy_cycles(&mut self, creator: Principal, cycles: u128) {
+        let mut usage = self.cycles_usage_of(&creator);
+        usage.deployed += cycles;
+        self.cycles_usage.insert(creator, usage);
+    }
+
+    pub fn record_top_up_cycles(
+        &mut self,
+        creator: Principal,
+        canister_id: Principal,
+        cycles: u128,
+    ) {
+        let mut usage = self.cycles_usage_of(&creator);
+        usage.topped_up += cycles;
+        self.cycles_usage.insert(creator, usage);
+        self.log_top_up_cycles(creator, canister_id, cycles);
+    }
+
+    /// Counts a top-up requested by `creator` against their top-up budget
+    /// before the cycles are sent. Admin top-ups pass `budgeted = false`.
+    pub fn reserve_top_up_cycles(
+        &mut self,
+        creator: Principal,
+        cycles: u128,
+        budgeted: bool,
+    ) -> Result<(), String> {
+        let mut usage = self.cycles_usage_of(&creator);
+        let topped_up = usage.topped_up.saturating_add(cycles);
+        if budgeted && topped_up > self.cycles_config.max_creator_top_up_cycles {
+            return Err(format!(
+                "Top-ups are limited to {} cycles per creator, {} already used",
+                self.cycles_config.max_creator_top_up_cycles, usage.topped_up
+            ));
+        }
+        usage.topped_up = topped_up;
+        self.cycles_usage.insert(creator, usage);
+        Ok(())
+    }
+
+    /// Gives back the cycles of a reserved top-up that could not be deposited.
+    pub fn release_top_up_cycles(&mut self, creator: Principal, cycles: u128) {
+        let mut usage = self.cycles_usage_of(&creator);
+        usage.topped_up = usage.topped_up.saturating_sub(cycles);
+        self.cycles_usage.insert(creator, usage);
+    }
+
+    pub fn log_top_up_cycles(&mut self, creator: Principal, canister_id: Principal, cycles: u128) {
+        self.append_block(
+            BTYPE_TOP_UP,
+            tx([
+                ("canister_id", principal_value(canister_id)),
+                ("creator", principal_value(creator)),
+                ("cycles", Value::Nat(cycles.into())),
+            ]),
+        );
+    }
+
+    pub fn set_collection_template(
+        &mut self,
+        name: String,
+        description: Option<String>,
+        defaults: ArgOverrides,
+    ) -> Result<CollectionTemplate, String> {
+        if name.is_empty() || name.len() > State::MAX_TEMPLATE_NAME_LEN {
+            return Err(format!(
+                "Template name must be between 1 and {} bytes",
+                State::MAX_TEMPLATE_NAME_LEN
+            ));
+        }
+        let now = ic_cdk::api::time();
+        let created_at = self
+            .collection_templates
+            .get(&name)
+            .map_or(now, |template| template.created_at);
+        let template = CollectionTemplate {
+            name: name.clone(),
+            description,
+            defaults,
+            created_at,
+            updated_at: now,
+        };
+        self.collection_templates.insert(name, template.clone());
+        Ok(template)
+    }
+
+    pub fn delete_collection_template(&mut self, name: &String) -> Result<(), String> {
+        self.collection_templates
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| String::from("Unknown template"))
+    }
+
+    pub fn get_collection_template(&self, name: &String) -> Option<CollectionTemplate> {
+        self.collection_templates.get(name)
+    }
+
+    pub fn collection_templates(&self) -> Vec<CollectionTemplate> {
+        self.collection_templates.iter().map(|(_, t)| t).collect()
+    }
+
+    pub fn monitor_config(&self) -> MonitorConfig {
+        self.monitor_config.clone()
+    }
+
+    pub fn set_monitor_config(&mut self, config: MonitorConfig) -> Result<(), String> {
+        if config.interval_secs == 0 {
+            return Err("Monitor interval must be at least one second".into());
+        }
+        self.monitor_config = config;
+        Ok(())
+    }
+
+    pub fn record_canister_health(&mut self, health: CanisterHealth) {
+        self.canister_health.insert(health.canister_id, health);
+    }
+
+    pub fn get_canister_health(&self, canister_id: &Principal) -> Option<CanisterHealth> {
+        self.canister_health.get(canister_id)
+    }
+
+    /// Canisters that could not be checked or stayed below the monitor
+    /// threshold after their last check.
+    pub fn at_risk_canisters(
+        &self,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<CanisterHealth> {
+        let take = self.get_current_take(take);
+        let threshold_cycles = self.monitor_config.threshold_cycles;
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.canister_health
+            .range((start, Bound::Unbounded))
+            .map(|(_, health)| health)
+            .filter(|health| {
+                health
+                    .balance
+                    .is_none_or(|balance| balance < threshold_cycles)
+            })
+            .take(take)
+            .collect()
+    }
+
+    pub fn add_deployment(&mut self, deployment: Deployment) {
+        self.deployments.insert(deployment.canister_id, deployment);
+    }
+
+    pub fn get_deployment(&self, canister_id: &Principal) -> Option<Deployment> {
+        self.deployments.get(canister_id)
+    }
+
+    pub fn remove_deployment(&mut self, canister_id: &Principal) -> Option<Deployment> {
+        self.deployments.remove(canister_id)
+    }
+
+    pub fn start_deployment_attempt(
+        &mut self,
+        canister_id: &Principal,
+        wasm_version: String,
+    ) -> Option<Deployment> {
+        let mut deployment = self.deployments.get(canister_id)?;
+        deployment.wasm_version = wasm_version;
+        deployment.status = DeploymentStatus::Created;
+        deployment.attempts += 1;
+        deployment.updated_at = ic_cdk::api::time();
+        self.deployments.insert(*canister_id, deployment.clone());
+        Some(deployment)
+    }
+
+    /// Moves a failed deployment to `Reclaiming`, see `Deployment::start_reclaim`.
+    pub fn start_reclaim(&mut self, canister_id: &Principal) -> Result<DeploymentStatus, String> {
+        let mut deployment = self
+            .deployments
+            .get(canister_id)
+            .ok_or_else(|| String::from("Unknown deployment"))?;
+        let failed = deployment.start_reclaim()?;
+        deployment.updated_at = ic_cdk::api::time();
+        self.deployments.insert(*canister_id, deployment);
+        Ok(failed)
+    }
+
+    pub fn set_deployment_status(&mut self, canister_id: &Principal, status: DeploymentStatus) {
+        if let Some(mut deployment) = self.deployments.get(canister_id) {
+            deployment.status = status;
+            deployment.updated_at = ic_cdk::api::time();
+            self.deployments.insert(*canister_id, deployment);
+        }
+    }
+
+    /// Marks a deployment as verified and registers its collection.
+    pub fn complete_deployment(&mut self, canister_id: &Principal, control: ControlStatus) {
+        self.set_deployment_status(canister_id, DeploymentStatus::Verified);
+        if let Some(deployment) = self.deployments.get(canister_id) {
+            let init_arg = Decode!(deployment.init_arg.as_slice(), InitArg).ok();
+            if self.reservation_config.enabled {
+                self.bind_reservations(
+                    deployment.creator,
+                    &deployment.icrc7_symbol,
+                    &deployment.icrc7_name,
+                    deployment.canister_id,
+                );
+            }
+            self.append_block(
+                BTYPE_DEPLOY,
+                tx([
+                    ("canister_id", principal_value(deployment.canister_id)),
+                    ("creator", principal_value(deployment.creator)),
+                    ("symbol", Value::Text(deployment.icrc7_symbol.clone())),
+                    ("wasm_version", Value::Text(deployment.wasm_version.clone())),
+                    ("controllers", controllers_value(&control)),
+                ]),
+            );
+            self.add_collection(CollectionInfo {
+                canister_id: deployment.canister_id,
+                creator: deployment.creator,
+                icrc7_symbol: deployment.icrc7_symbol,
+                icrc7_name: deployment.icrc7_name,
+                created_at: deployment.updated_at,
+                wasm_version: deployment.wasm_version,
+                control: Some(control),
+                settings: deployment.settings,
+                icrc7_description: init_arg
+                    .as_ref()
+                    .and_then(|arg| arg.icrc7_description.clone()),
+                icrc7_logo: init_arg.and_then(|arg| arg.icrc7_logo),
+                metadata_refreshed_at: None,
+                featured: None,
+                verified: None,
+                delisted: None,
+            });
+        }
+    }
+
+    /// Lists deployments that are not verified yet, optionally only those of `creator`.
+    pub fn pending_deployments(
+        &self,
+        creator: Option<Principal>,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<Deployment> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.deployments
+            .range((start, Bound::Unbounded))
+            .map(|(_, deployment)| deployment)
+            .filter(|deployment| deployment.status != DeploymentStatus::Verified)
+            .filter(|deployment| creator.is_none_or(|creator| deployment.creator == creator))
+            .take(take)
+            .collect()
+    }
+
+    pub fn is_admin(&self, principal: &Principal) -> bool {
+        self.admins.contains_key(principal)
+    }
+
+    pub fn add_admin(&mut self, principal: Principal) {
+        self.admins.insert(principal, ic_cdk::api::time());
+    }
+
+    pub fn remove_admin(&mut self, principal: &Principal) -> bool {
+        self.admins.remove(principal).is_some()
+    }
+
+    pub fn admins(&self) -> Vec<Principal> {
+        self.admins.iter().map(|(principal, _)| principal).collect()
+    }
+
+    pub fn is_allowlisted(&self, principal: &Principal) -> bool {
+        self.allowlist.contains_key(principal)
+    }
+
+    pub fn add_to_allowlist(&mut self, principals: Vec<Principal>) {
+        let now = ic_cdk::api::time();
+        for principal in principals {
+            self.allowlist.insert(principal, now);
+        }
+    }
+
+    pub fn remove_from_allowlist(&mut self, principals: Vec<Principal>) {
+        for principal in principals {
+            self.allowlist.remove(&principal);
+        }
+    }
+
+    pub fn allowlist(&self, prev: Option<Principal>, take: Option<u64>) -> Vec<Principal> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.allowlist
+            .range((start, Bound::Unbounded))
+            .take(take)
+            .map(|(principal, _)| principal)
+            .collect()
+    }
+
+    pub fn set_deployment_quota(&mut self, quota: Option<DeploymentQuota>) -> Result<(), String> {
+        if let Some(quota) = &quota {
+            if quota.window_secs == 0 {
+                return Err("Quota window must be at least one second".into());
+            }
+        }
+        self.deployment_quota = quota;
+        Ok(())
+    }
+
+    fn window_start(&self, quota: &DeploymentQuota, now: u64) -> u64 {
+        now.saturating_sub(quota.window_secs.saturating_mul(1_000_000_000))
+    }
+
+    /// Number of deployments `creator` started within the current quota window.
+    pub fn deployments_in_window(&self, creator: Principal) -> u32 {
+        let quota = match &self.deployment_quota {
+            Some(quota) => quota,
+            None => return 0,
+        };
+        let start = self.window_start(quota, ic_cdk::api::time());
+        self.creator_deployments
+            .range((creator, start)..=(creator, u64::MAX))
+            .count() as u32
+    }
+
+    /// Counts a new deployment of `creator` against the quota, failed
+    /// deployments included. Entries older than the window are dropped.
+    pub fn reserve_deployment_slot(&mut self, creator: Principal) -> Result<(), String> {
+        let quota = match self.deployment_quota.clone() {
+            Some(quota) => quota,
+            None => return Ok(()),
+        };
+        let now = ic_cdk::api::time();
+        let start = self.window_start(&quota, now);
+        let expired: Vec<(Principal, u64)> = self
+            .creator_deployments
+            .range((creator, 0)..(creator, start))
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            self.creator_deployments.remove(&key);
+        }
+
+        if self.deployments_in_window(creator) >= quota.max_deployments {
+            return Err(format!(
+                "Deployment quota of {} per {} seconds reached",
+                quota.max_deployments, quota.window_secs
+            ));
+        }
+        // Messages executed in the same round share the same time.
+        let mut at = now;
+        while self.creator_deployments.contains_key(&(creator, at)) {
+            at += 1;
+        }
+        self.creator_deployments.insert((creator, at), ());
+        Ok(())
+    }
+
+    pub fn set_reservation_config(&mut self, config: ReservationConfig) -> Result<(), String> {
+        if config.ttl_secs == 0 {
+            return Err("Reservation TTL must be at least one second".into());
+        }
+        let enabling = config.enabled && !self.reservation_config.enabled;
+        self.reservation_config = config;
+        // Collections listed before enforcement hold their symbol and name,
+        // the first registered one wins on conflicts.
+        if enabling {
+            let collections: Vec<CollectionInfo> =
+                self.collections.iter().map(|(_, info)| info).collect();
+            for info in collections {
+                self.bind_reservations(
+                    info.creator,
+                    &info.icrc7_symbol,
+                    &info.icrc7_name,
+                    info.canister_id,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_symbol_reservation(&self, symbol: &str) -> Option<Reservation> {
+        live_reservation(
+            &self.symbol_reservations,
+            &symbol_key(symbol),
+            ic_cdk::api::time(),
+        )
+    }
+
+    pub fn get_name_reservation(&self, name: &str) -> Option<Reservation> {
+        live_reservation(
+            &self.name_reservations,
+            &name_key(name),
+            ic_cdk::api::time(),
+        )
+    }
+
+    fn check_reservation(
+        reservation: Option<Reservation>,
+        owner: Principal,
+        what: &str,
+    ) -> Result<(), String> {
+        match reservation {
+            None => Ok(()),
+            Some(reservation) if reservation.owner != owner => {
+                Err(format!("The {} is reserved by another creator", what))
+            }
+            Some(reservation) if reservation.collection.is_some() => {
+                Err(format!("The {} is already used by a collection", what))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Checks that `owner` could reserve `symbol` and `name`, without
+    /// reserving them.
+    pub fn check_collection_identity(
+        &self,
+        owner: Principal,
+        symbol: &str,
+        name: &str,
+    ) -> Result<(), String> {
+        State::check_reservation(self.get_symbol_reservation(symbol), owner, "symbol")?;
+        State::check_reservation(self.get_name_reservation(name), owner, "name")
+    }
+
+    /// Reserves `symbol` and `name` for `owner` until the reservation TTL
+    /// elapses. Reserving a pair again keeps the expiry of the reservations
+    /// `owner` already holds, so a claim never outlives its TTL.
+    pub fn reserve_collection_identity(
+        &mut self,
+        owner: Principal,
+        symbol: &str,
+        name: &str,
+    ) -> Result<u64, String> {
+        self.check_collection_identity(owner, symbol, name)?;
+        let now = ic_cdk::api::time();
+        let ttl = self
+            .reservation_config
+            .ttl_secs
+            .saturating_mul(1_000_000_000);
+        let reservation = |held: Option<Reservation>| {
+            let reserved_at = held.map_or(now, |held| held.reserved_at);
+            Reservation {
+                owner,
+                collection: None,
+                reserved_at,
+                expires_at: Some(reserved_at.saturating_add(ttl)),
+            }
+        };
+        let symbol_reservation = reservation(self.get_symbol_reservation(symbol));
+        let name_reservation = reservation(self.get_name_reservation(name));
+        let expires_at = symbol_reservation
+            .reserved_at
+            .min(name_reservation.reserved_at)
+            .saturating_add(ttl);
+        self.symbol_reservations
+            .insert(symbol_key(symbol), symbol_reservation);
+        self.name_reservations
+            .insert(name_key(name), name_reservation);
+        Ok(expires_at)
+    }
+
+    /// Ties the reservations of a deployed collection to it, unless another
+    /// creator holds them.
+    fn bind_reservations(
+        &mut self,
+        owner: Principal,
+        symbol: &str,
+        name: &str,
+        canister_id: Principal,
+    ) {
+        let now = ic_cdk::api::time();
+        let reservation = Reservation {
+            owner,
+            collection: Some(canister_id),
+            reserved_at: now,
+            expires_at: None,
+        };
+        let symbol_key = symbol_key(symbol);
+        let name_key = name_key(name);
+        if live_reservation(&self.symbol_reservations, &symbol_key, now)
+            .is_none_or(|r| r.owner == owner && r.collection.is_none())
+        {
+            self.symbol_reservations
+                .insert(symbol_key, reservation.clone());
+        }
+        if live_reservation(&self.name_reservations, &name_key, now)
+            .is_none_or(|r| r.owner == owner && r.collection.is_none())
+        {
+            self.name_reservations.insert(name_key, reservation);
+        }
+    }
+
+    /// Drops the unused reservations `owner` holds on `symbol` and `name`.
+    pub fn release_reservation(
+        &mut self,
+        owner: Principal,
+        symbol: Option<String>,
+        name: Option<String>,
+    ) {
+        let releasable = |r: &Reservation| r.owner == owner && r.collection.is_none();
+        if let Some(symbol) = symbol {
+            let symbol_key = symbol_key(&symbol);
+            if self
+                .symbol_reservations
+                .get(&symbol_key)
+                .is_some_and(|r| releasable(&r))
+            {
+                self.symbol_reservations.remove(&symbol_key);
+            }
+        }
+        if let Some(name) = name {
+            let name_key = name_key(&name);
+            if self
+                .name_reservations
+                .get(&name_key)
+                .is_some_and(|r| releasable(&r))
+            {
+                self.name_reservations.remove(&name_key);
+            }
+        }
+    }
+
+    /// Frees the symbol and name held by a collection leaving the listing.
+    pub fn release_collection_reservations(&mut self, info: &CollectionInfo) {
+        let held = |r: &Reservation| r.collection == Some(info.canister_id);
+        let symbol_key = symbol_key(&info.icrc7_symbol);
+        if self
+            .symbol_reservations
+            .get(&symbol_key)
+            .is_some_and(|r| held(&r))
+        {
+            self.symbol_reservations.remove(&symbol_key);
+        }
+        let name_key = name_key(&info.icrc7_name);
+        if self
+            .name_reservations
+            .get(&name_key)
+            .is_some_and(|r| held(&r))
+        {
+            self.name_reservations.remove(&name_key);
+        }
+    }
+
+    /// Moves the symbol reservations made while symbols were still matched
+    /// case-sensitively to their normalized key. On conflicts, a reservation
+    /// held by a collection wins over a claim.
+    pub fn normalize_symbol_reservations(&mut self) {
+        let stale: Vec<(String, Reservation)> = self
+            .symbol_reservations
+            .iter()
+            .filter(|(symbol, _)| *symbol != symbol_key(symbol))
+            .collect();
+        for (symbol, reservation) in stale {
+            self.symbol_reservations.remove(&symbol);
+            let symbol_key = symbol_key(&symbol);
+            if self
+                .symbol_reservations
+                .get(&symbol_key)
+                .is_none_or(|held| held.collection.is_none() && reservation.collection.is_some())
+            {
+                self.symbol_reservations.insert(symbol_key, reservation);
+            }
+        }
+    }
+
+    /// Appends a launchpad block to the log, chained to the previous one,
+    /// and certifies the new tip.
+    pub fn append_block(&mut self, btype: &str, tx: Map) -> u128 {
+        let (index, phash) = match self.blocks.last_key_value() {
+            Some((index, block)) => (index + 1, Some(block.hash())),
+            None => (0, None),
+        };
+        let mut block = Map::new();
+        if let Some(phash) = phash {
+            block.insert("phash".to_string(), Value::Blob(ByteBuf::from(phash)));
+        }
+        block.insert("btype".to_string(), Value::Text(btype.to_string()));
+        block.insert("ts".to_string(), Value::Nat(ic_cdk::api::time().into()));
+        block.insert("tx".to_string(), Value::Map(tx));
+        let block = Block::from(block);
+        let hash = block.hash();
+        self.blocks.insert(index, block);
+        certify_tip(index, hash);
+        index
+    }
+
+    /// Certifies the current log tip again, certified data does not survive upgrades.
+    pub fn certify_log_tip(&self) {
+        if let Some((index, block)) = self.blocks.last_key_value() {
+            certify_tip(index, block.hash());
+        }
+    }
+
+    pub fn log_length(&self) -> u128 {
+        self.blocks
+            .last_key_value()
+            .map_or(0, |(index, _)| index + 1)
+    }
+
+    pub fn icrc3_get_blocks(&self, args: GetBlocksArgs) -> GetBlocksResult {
+        let max_blocks = State::DEFAULT_MAX_TAKE_VALUE as usize;
+        let mut blocks: Vec<QueryBlock> = vec![];
+        for arg in args {
+            if blocks.len() >= max_blocks {
+                break;
+            }
+            let end = arg.start.saturating_add(arg.length);
+            blocks.extend(
+                self.blocks
+                    .range(arg.start..end)
+                    .take(max_blocks - blocks.len())
+                    .map(|(id, block)| QueryBlock {
+                        id,
+                        block: block.into_inner(),
+                    }),
+            );
+        }
+        GetBlocksResult {
+            blocks,
+            log_length: self.log_length(),
+            archived_blocks: vec![],
+        }
+    }
+
+    pub fn add_spare_canister(&mut self, canister_id: Principal) {
+        self.spare_canisters
+            .insert(canister_id, ic_cdk::api::time());
+    }
+
+    pub fn take_spare_canister(&mut self) -> Option<Principal> {
+        let canister_id = self.spare_canisters.iter().next().map(|(id, _)| id)?;
+        self.spare_canisters.remove(&canister_id);
+        Some(canister_id)
+    }
+
+    pub fn spare_canisters(&self) -> Vec<Principal> {
+        self.spare_canisters.iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn next_collection(&self, prev: Option<Principal>) -> Option<CollectionInfo> {
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.collections
+            .range((start, Bound::Unbounded))
+            .next()
+            .map(|(_, info)| info)
+    }
+
+    pub fn get_collection(&self, canister_id: &Principal) -> Option<CollectionInfo> {
+        self.collections.get(canister_id)
+    }
+
+    pub fn total_collections(&self) -> u64 {
+        self.collections.len() - self.delisted_collections
+    }
+
+    pub fn list_collections(
+        &self,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<CollectionInfo> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.collections
+            .range((start, Bound::Unbounded))
+            .map(|(_, info)| info)
+            .filter(|info| !info.is_delisted())
+            .take(take)
+            .collect()
+    }
+
+    pub fn search_collections(
+        &self,
+        filter: CollectionFilter,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> CollectionSearchPage {
+        let take = self.get_current_take(take);
+        // Walk the creator index when the search is limited to one creator.
+        if let Some(creator) = filter.creator {
+            let start = match prev {
+                Some(prev) => Bound::Excluded((creator, prev)),
+                None => Bound::Included((creator, Principal::management_canister())),
+            };
+            let collections = self
+                .creator_collections
+                .range((start, Bound::Unbounded))
+                .take_while(|((owner, _), _)| *owner == creator)
+                .filter_map(|((_, canister_id), _)| self.collections.get(&canister_id));
+            return State::search_page(collections, &filter, take);
+        }
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        let collections = self
+            .collections
+            .range((start, Bound::Unbounded))
+            .map(|(_, info)| info);
+        State::search_page(collections, &filter, take)
+    }
+
+    // Collects up to `take` matches, scanning at most `MAX_SEARCH_SCAN`
+    // collections. `next` is the last one scanned if the scan stopped early.
+    fn search_page(
+        collections: impl Iterator<Item = CollectionInfo>,
+        filter: &CollectionFilter,
+        take: usize,
+    ) -> CollectionSearchPage {
+        let mut page = CollectionSearchPage::default();
+        for (scanned, info) in collections.enumerate() {
+            if page.collections.len() == take || scanned == State::MAX_SEARCH_SCAN {
+                return page;
+            }
+            page.next = Some(info.canister_id);
+            if info.matches(filter) {
+                page.collections.push(info);
+            }
+        }
+        page.next = None;
+        page
+    }
+
+    pub fn set_collection_flags(
+        &mut self,
+        canister_id: &Principal,
+        featured: Option<bool>,
+        verified: Option<bool>,
+    ) -> Result<CollectionInfo, String> {
+        let mut info = self
+            .collections
+            .get(canister_id)
+            .ok_or_else(|| String::from("Unknown collection"))?;
+        if featured.is_some() {
+            info.featured = featured;
+        }
+        if verified.is_some() {
+            info.verified = verified;
+        }
+        self.collections.insert(*canister_id, info.clone());
+        Ok(info)
+    }
+
+    /// Replaces the metadata captured at deploy time. A changed symbol or name
+    /// moves the collection's reservations when they are enforced.
+    pub fn update_collection_metadata(
+        &mut self,
+        canister_id: &Principal,
+        metadata: CollectionMetadata,
+    ) -> Result<CollectionInfo, String> {
+        let mut info = self
+            .collections
+            .get(canister_id)
+            .ok_or_else(|| String::from("Unknown collection"))?;
+        if self.reservation_config.enabled
+            && !info.is_delisted()
+            && (info.icrc7_symbol != metadata.icrc7_symbol
+                || info.icrc7_name != metadata.icrc7_name)
+        {
+            // Keep the current metadata rather than list the collection under
+            // a symbol or name held by someone else.
+            let held_elsewhere = |reservation: Option<Reservation>| {
+                reservation.is_some_and(|r| {
+                    r.owner != info.creator
+                        || r.collection
+                            .is_some_and(|collection| collection != info.canister_id)
+                })
+            };
+            if held_elsewhere(self.get_symbol_reservation(&metadata.icrc7_symbol)) {
+                return Err("The new symbol is reserved by another creator or collection".into());
+            }
+            if held_elsewhere(self.get_name_reservation(&metadata.icrc7_name)) {
+                return Err("The new name is reserved by another creator or collection".into());
+            }
+            self.release_collection_reservations(&info);
+            self.bind_reservations(
+                info.creator,
+                &metadata.icrc7_symbol,
+                &metadata.icrc7_name,
+                info.canister_id,
+            );
+        }
+        info.icrc7_symbol = metadata.icrc7_symbol;
+        info.icrc7_name = metadata.icrc7_name;
+        info.icrc7_description = metadata.icrc7_description;
+        info.icrc7_logo = metadata.icrc7_logo;
+        info.metadata_refreshed_at = Some(ic_cdk::api::time());
+        self.collections.insert(*canister_id, info.clone());
+        Ok(info)
+    }
+
+    pub fn collections_of(
+        &self,
+        creator: Principal,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<CollectionInfo> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded((creator, prev)),
+            None => Bound::Included((creator, Principal::management_canister())),
+        };
+        self.creator_collections
+            .range((start, Bound::Unbounded))
+            .take_while(|((owner, _), _)| *owner == creator)
+            .filter_map(|((_, canister_id), _)| self.collections.get(&canister_id))
+            .filter(|info| !info.is_delisted())
+            .take(take)
+            .collect()
+    }
+
+    pub fn record_upgrade(
+        &mut self,
+        canister_id: Principal,
+        wasm_version: String,
+        result: Result<(), String>,
+    ) -> CollectionUpgrade {
+        let status = match result {
+            Ok(()) => {
+                if let Some(mut info) = self.collections.get(&canister_id) {
+                    info.wasm_version = wasm_version.clone();
+                    self.collections.insert(canister_id, info);
+                }
+                self.append_block(
+                    BTYPE_UPGRADE,
+                    tx([
+                        ("canister_id", principal_value(canister_id)),
+                        ("wasm_version", Value::Text(wasm_version.clone())),
+                    ]),
+                );
+                UpgradeStatus::Upgraded
+            }
+            Err(message) => UpgradeStatus::Failed { message },
+        };
+        let upgrade = CollectionUpgrade {
+            canister_id,
+            wasm_version,
+            status,
+            updated_at: ic_cdk::api::time(),
+        };
+        self.collection_upgrades
+            .insert(canister_id, upgrade.clone());
+        upgrade
+    }
+
+    pub fn get_collection_upgrade(&self, canister_id: &Principal) -> Option<CollectionUpgrade> {
+        self.collection_upgrades.get(canister_id)
+    }
+
+    pub fn collection_upgrades(
+        &self,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<CollectionUpgrade> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.collection_upgrades
+            .range((start, Bound::Unbounded))
+            .take(take)
+            .map(|(_, upgrade)| upgrade)
+            .collect()
+    }
+
+    pub fn upgrade_job(&self) -> Option<UpgradeJob> {
+        self.upgrade_job.clone()
+    }
+
+    pub fn is_upgrade_running(&self) -> bool {
+        matches!(self.upgrade_job, Some(ref job) if job.finished_at.is_none())
+    }
+
+    pub fn start_upgrade_job(&mut self, wasm_version: String) -> Result<UpgradeJob, String> {
+        if self.is_upgrade_running() {
+            return Err("An upgrade of all collections is already running".into());
+        }
+        let job = UpgradeJob {
+            wasm_version,
+            started_at: ic_cdk::api::time(),
+            finished_at: None,
+            cursor: None,
+            upgraded: 0,
+            skipped: 0,
+            failed: 0,
+        };
+        self.upgrade_job = Some(job.clone());
+        Ok(job)
+    }
+
+    // Moves the job cursor past `canister_id`. A `None` status means the
+    // collection was already on the target version.
+    pub fn advance_upgrade_job(&mut self, canister_id: Principal, status: Option<&UpgradeStatus>) {
+        if let Some(ref mut job) = self.upgrade_job {
+            job.cursor = Some(canister_id);
+            match status {
+                None => job.skipped += 1,
+                Some(UpgradeStatus::Upgraded) => job.upgraded += 1,
+                Some(UpgradeStatus::Failed { .. }) => job.failed += 1,
+            }
+        }
+    }
+
+    pub fn finish_upgrade_job(&mut self) -> bool {
+        match self.upgrade_job {
+            Some(ref mut job) if job.finished_at.is_none() => {
+                job.finished_at = Some(ic_cdk::api::time());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn mint_range(
+        map: &StableBTreeMap<(Principal, u64), Vec<u8>, Memory>,
+        owner: Principal,
+    ) -> impl Iterator<Item = ((Principal, u64), Vec<u8>)> + '_ {
+        map.range((owner, 0)..=(owner, u64::MAX))
+    }
+
+    pub fn mint_manifest_len(&self, creator: Principal) -> u64 {
+        Self::mint_range(&self.mint_manifests, creator).count() as u64
+    }
+
+    /// Appends tokens to the manifest `creator` is preparing for their next
+    /// deployment, returning its new length.
+    pub fn upload_mint_manifest_chunk(
+        &mut self,
+        creator: Principal,
+        chunk: Vec<MintArg>,
+    ) -> Result<u64, String> {
+        let len = self.mint_manifest_len(creator);
+        if len as usize + chunk.len() > MAX_MINT_MANIFEST_LEN {
+            return Err(format!(
+                "A mint manifest holds at most {} tokens",
+                MAX_MINT_MANIFEST_LEN
+            ));
+        }
+        for (index, mint) in (len..).zip(chunk) {
+            self.mint_manifests
+                .insert((creator, index), Encode!(&mint).unwrap());
+        }
+        Ok(self.mint_manifest_len(creator))
+    }
+
+    pub fn mint_manifest_of(&self, creator: Principal) -> Vec<MintArg> {
+        Self::mint_range(&self.mint_manifests, creator)
+            .map(|(_, bytes)| Decode!(&bytes, MintArg).unwrap())
+            .collect()
+    }
+
+    /// Removes the manifest `creator` uploaded and returns it.
+    pub fn take_mint_manifest(&mut self, creator: Principal) -> Vec<MintArg> {
+        let manifest = self.mint_manifest_of(creator);
+        self.clear_mint_manifest(creator);
+        manifest
+    }
+
+    /// Puts back a manifest taken by a deployment that failed, ahead of any
+    /// chunk `creator` uploaded since.
+    pub fn restore_mint_manifest(&mut self, creator: Principal, mut manifest: Vec<MintArg>) {
+        manifest.extend(self.take_mint_manifest(creator));
+        for (index, mint) in (0..).zip(manifest) {
+            self.mint_manifests
+                .insert((creator, index), Encode!(&mint).unwrap());
+        }
+    }
+
+    pub fn clear_mint_manifest(&mut self, creator: Principal) {
+        let keys: Vec<_> = Self::mint_range(&self.mint_manifests, creator)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            self.mint_manifests.remove(&key);
+        }
+    }
+
+    /// Queues `manifest` for minting into `canister_id`. The job stays
+    /// pending until the deployment is verified, minting is then handed over
+    /// to `minting_account`, or to the creator when unset.
+    pub fn queue_mint_manifest(
+        &mut self,
+        canister_id: Principal,
+        creator: Principal,
+        minting_account: Option<Account>,
+        manifest: Vec<MintArg>,
+    ) {
+        let now = ic_cdk::api::time();
+        self.mint_jobs.insert(
+            canister_id,
+            MintJob {
+                canister_id,
+                creator,
+                total: manifest.len() as u64,
+                minted: 0,
+                failed: 0,
+                failures: vec![],
+                status: MintJobStatus::Pending,
+                minting_account,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        for (index, mint) in manifest.into_iter().enumerate() {
+            self.mint_queue
+                .insert((canister_id, index as u64), Encode!(&mint).unwrap());
+        }
+    }
+
+    pub fn get_mint_job(&self, canister_id: &Principal) -> Option<MintJob> {
+        self.mint_jobs.get(canister_id)
+    }
+
+    pub fn running_mint_jobs(&self) -> Vec<MintJob> {
+        self.mint_jobs
+            .iter()
+            .map(|(_, job)| job)
+            .filter(|job| job.status == MintJobStatus::Running)
+            .collect()
+    }
+
+    pub fn set_mint_job_status(&mut self, canister_id: &Principal, status: MintJobStatus) {
+        if let Some(mut job) = self.mint_jobs.get(canister_id) {
+            job.status = status;
+            job.updated_at = ic_cdk::api::time();
+            self.mint_jobs.insert(*canister_id, job);
+        }
+    }
+
+    /// Starts a pending job, or resumes one that was stopped by a failure.
+    pub fn start_mint_job(&mut self, canister_id: &Principal) -> Result<(), String> {
+        let job = self
+            .mint_jobs
+            .get(canister_id)
+            .ok_or_else(|| String::from("Unknown mint job"))?;
+        match job.status {
+            MintJobStatus::Pending | MintJobStatus::Failed { .. } => {
+                self.set_mint_job_status(canister_id, MintJobStatus::Running);
+                Ok(())
+            }
+            MintJobStatus::Running => Err("The mint job is already running".into()),
+            MintJobStatus::Completed => Err("The mint job is already completed".into()),
+        }
+    }
+
+    pub fn next_mint_batch(&self, canister_id: Principal, size: usize) -> Vec<(u64, MintArg)> {
+        Self::mint_range(&self.mint_queue, canister_id)
+            .take(size)
+            .map(|((_, index), bytes)| (index, Decode!(&bytes, MintArg).unwrap()))
+            .collect()
+    }
+
+    /// Removes a token from the queue once the collection has answered for
+    /// it, recording whether it was minted.
+    pub fn record_mint(
+        &mut self,
+        canister_id: Principal,
+        index: u64,
+        token_id: u128,
+        result: Result<(), String>,
+    ) {
+        self.mint_queue.remove(&(canister_id, index));
+        if let Some(mut job) = self.mint_jobs.get(&canister_id) {
+            match result {
+                Ok(()) => job.minted += 1,
+                Err(message) => {
+                    job.failed += 1;
+                    if job.failures.len() < MINT_JOB_MAX_FAILURES {
+                        job.failures.push(MintFailure { token_id, message });
+                    }
+                }
+            }
+            job.updated_at = ic_cdk::api::time();
+            self.mint_jobs.insert(canister_id, job);
+        }
+    }
+
+    pub fn remove_mint_job(&mut self, canister_id: &Principal) -> Option<MintJob> {
+        let keys: Vec<_> = Self::mint_range(&self.mint_queue, *canister_id)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            self.mint_queue.remove(&key);
+        }
+        self.mint_jobs.remove(canister_id)
+    }
+
+    pub fn accept_minting_role(&mut self, owner: Principal, deployer: Principal) {
+        self.minting_consents
+            .insert((owner, deployer), ic_cdk::api::time());
+    }
+
+    pub fn revoke_minting_role(&mut self, owner: Principal, deployer: &Principal) -> bool {
+        self.minting_consents.remove(&(owner, *deployer)).is_some()
+    }
+
+    /// Whether `owner` agreed to be the minting authority of collections
+    /// deployed by `deployer`. Nobody needs to consent for themselves.
+    pub fn has_minting_consent(&self, owner: Principal, deployer: Principal) -> bool {
+        owner == deployer || self.minting_consents.contains_key(&(owner, deployer))
+    }
+
+    pub fn report_collection(
+        &mut self,
+        reporter: Principal,
+        canister_id: Principal,
+        reason: String,
+    ) -> Result<u64, String> {
+        let info = self
+            .collections
+            .get(&canister_id)
+            .ok_or_else(|| String::from("Unknown collection"))?;
+        if info.is_delisted() {
+            return Err("The collection is already delisted".into());
+        }
+        if reason.trim().is_empty() || reason.len() > State::MAX_REPORT_REASON_LEN {
+            return Err(format!(
+                "The reason must be between 1 and {} bytes",
+                State::MAX_REPORT_REASON_LEN
+            ));
+        }
+        if self.open_reports.contains_key(&(canister_id, reporter)) {
+            return Err("The caller already has an open report on this collection".into());
+        }
+        let open = self
+            .open_reports
+            .range((canister_id, Principal::management_canister())..)
+            .take_while(|((reported, _), _)| *reported == canister_id)
+            .take(State::MAX_OPEN_REPORTS_PER_COLLECTION)
+            .count();
+        if open == State::MAX_OPEN_REPORTS_PER_COLLECTION {
+            return Err("The collection already has too many open reports".into());
+        }
+        let id = self.reports.last_key_value().map_or(0, |(id, _)| id + 1);
+        self.reports.insert(
+            id,
+            CollectionReport {
+                id,
+                canister_id,
+                reporter,
+                reason,
+                status: ReportStatus::Open,
+                created_at: ic_cdk::api::time(),
+                resolved_by: None,
+                resolved_at: None,
+            },
+        );
+        self.open_reports.insert((canister_id, reporter), id);
+        self.report_queue.insert(id, ());
+        Ok(id)
+    }
+
+    /// Lists open reports, oldest first.
+    pub fn review_queue(&self, prev: Option<u64>, take: Option<u64>) -> Vec<CollectionReport> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.report_queue
+            .range((start, Bound::Unbounded))
+            .filter_map(|(id, _)| self.reports.get(&id))
+            .take(take)
+            .collect()
+    }
+
+    /// Queues the reports opened before the review queue was indexed.
+    pub fn index_report_queue(&mut self) {
+        if self.report_queue.is_empty() {
+            let open: Vec<u64> = self.open_reports.iter().map(|(_, id)| id).collect();
+            for id in open {
+                self.report_queue.insert(id, ());
+            }
+        }
+    }
+
+    pub fn get_report(&self, id: u64) -> Option<CollectionReport> {
+        self.reports.get(&id)
+    }
+
+    fn resolve_report(&mut self, id: u64, status: ReportStatus, moderator: Principal) {
+        if let Some(mut report) = self.reports.get(&id) {
+            self.open_reports
+                .remove(&(report.canister_id, report.reporter));
+            self.report_queue.remove(&id);
+            report.status = status;
+            report.resolved_by = Some(moderator);
+            report.resolved_at = Some(ic_cdk::api::time());
+            self.reports.insert(id, report);
+        }
+    }
+
+    fn log_moderation(
+        &mut self,
+        canister_id: Principal,
+        action: ModerationAction,
+        reason: String,
+        moderator: Principal,
+    ) {
+        let id = self
+            .moderation_log
+            .last_key_value()
+            .map_or(0, |(id, _)| id + 1);
+        self.moderation_log.insert(
+            id,
+            ModerationEntry {
+                id,
+                canister_id,
+                action,
+                reason,
+                moderator,
+                timestamp: ic_cdk::api::time(),
+            },
+        );
+    }
+
+    pub fn dismiss_report(
+        &mut self,
+        id: u64,
+        reason: String,
+        moderator: Principal,
+    ) -> Result<CollectionReport, String> {
+        let report = self
+            .reports
+            .get(&id)
+            .ok_or_else(|| String::from("Unknown report"))?;
+        if report.status != ReportStatus::Open {
+            return Err("The report is already resolved".into());
+        }
+        self.resolve_report(id, ReportStatus::Dismissed, moderator);
+        self.log_moderation(
+            report.canister_id,
+            ModerationAction::ReportDismissed { report_id: id },
+            reason,
+            moderator,
+        );
+        Ok(self.reports.get(&id).unwrap())
+    }
+
+    /// Hides a collection from the listings and frees its symbol and name.
+    /// Its open reports are resolved as actioned.
+    pub fn delist_collection(
+        &mut self,
+        canister_id: Principal,
+        reason: String,
+        moderator: Principal,
+    ) -> Result<CollectionInfo, String> {
+        let mut info = self
+            .collections
+            .get(&canister_id)
+            .ok_or_else(|| String::from("Unknown collection"))?;
+        if info.is_delisted() {
+            return Err("The collection is already delisted".into());
+        }
+        let open: Vec<u64> = self
+            .open_reports
+            .range((canister_id, Principal::management_canister())..)
+            .take_while(|((reported, _), _)| *reported == canister_id)
+            .map(|(_, id)| id)
+            .collect();
+        for id in open {
+            self.resolve_report(id, ReportStatus::Actioned, moderator);
+        }
+        self.release_collection_reservations(&info);
+        info.delisted = Some(Delisting {
+            reason: reason.clone(),
+            moderator,
+            delisted_at: ic_cdk::api::time(),
+        });
+        self.collections.insert(canister_id, info.clone());
+        self.delisted_collections += 1;
+        self.log_moderation(canister_id, ModerationAction::Delisted, reason, moderator);
+        Ok(info)
+    }
+
+    /// Lists a delisted collection again. Its symbol and name are only
+    /// reserved again if nobody took them meanwhile.
+    pub fn relist_collection(
+        &mut self,
+        canister_id: Principal,
+        reason: String,
+        moderator: Principal,
+    ) -> Result<CollectionInfo, String> {
+        let mut info = self
+            .collections
+            .get(&canister_id)
+            .ok_or_else(|| String::from("Unknown collection"))?;
+        if !info.is_delisted() {
+            return Err("The collection is not delisted".into());
+        }
+        if self.reservation_config.enabled {
+            self.bind_reservations(
+                info.creator,
+                &info.icrc7_symbol,
+                &info.icrc7_name,
+                canister_id,
+            );
+        }
+        info.delisted = None;
+        self.collections.insert(canister_id, info.clone());
+        self.delisted_collections -= 1;
+        self.log_moderation(canister_id, ModerationAction::Relisted, reason, moderator);
+        Ok(info)
+    }
+
+    pub fn moderation_log(&self, prev: Option<u64>, take: Option<u64>) -> Vec<ModerationEntry> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.moderation_log
+            .range((start, Bound::Unbounded))
+            .map(|(_, entry)| entry)
+            .take(take)
+            .collect()
+    }
+
+    pub fn record_collection_stats(&mut self, stats: CollectionStats) {
+        self.collection_stats.insert(stats.canister_id, stats);
+    }
+
+    pub fn get_collection_stats(&self, canister_id: &Principal) -> Option<CollectionStats> {
+        self.collection_stats.get(canister_id)
+    }
+
+    pub fn list_collection_stats(
+        &self,
+        prev: Option<Principal>,
+        take: Option<u64>,
+    ) -> Vec<CollectionStats> {
+        let take = self.get_current_take(take);
+        let start = match prev {
+            Some(prev) => Bound::Excluded(prev),
+            None => Bound::Unbounded,
+        };
+        self.collection_stats
+            .range((start, Bound::Unbounded))
+            .map(|(_, stats)| stats)
+            .take(take)
+            .collect()
+    }
+
+    /// Sums the cached stats of every registered collection, dropping the
+    /// stats of collections that are no longer registered.
+    pub fn aggregate_collection_stats(&mut self) -> LaunchpadStats {
+        let stale: Vec<Principal> = self
+            .collection_stats
+            .iter()
+            .map(|(canister_id, _)| canister_id)
+            .filter(|canister_id| !self.collections.contains_key(canister_id))
+            .collect();
+        for canister_id in stale {
+            self.collection_stats.remove(&canister_id);
+        }
+
+        let mut totals = LaunchpadStats {
+            refreshed_at: ic_cdk::api::time(),
+            ..Default::default()
+        };
+        for (_, stats) in self.collection_stats.iter() {
+            totals.collections += 1;
+            if stats.error.is_some() {
+                totals.unreachable += 1;
+            }
+            totals.total_supply += stats.total_supply.unwrap_or_default();
+            totals.holders += stats.holders.unwrap_or_default();
+            totals.transactions += stats.transactions.unwrap_or_default();
+            totals.archives += stats.archives.unwrap_or_default();
+            totals.cycles = totals
+                .cycles
+                .saturating_add(stats.cycles.unwrap_or_default());
+        }
+        self.launchpad_stats = Some(totals.clone());
+        totals
+    }
+}
+
+thread_local! {
+    pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    pub static STATE: RefCell<State> = RefCell::default();
+}
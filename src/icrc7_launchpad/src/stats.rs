@@ -0,0 +1,98 @@
+use std::{cell::Cell, time::Duration};
+
+use candid::{CandidType, Deserialize, Principal};
+use icrc7_types::icrc3_types::GetBlocksArgs;
+
+use crate::{
+    deploy::query_collection,
+    monitor::{archives_of, wallet_balance},
+    state::STATE,
+    types::CollectionStats,
+};
+
+pub const STATS_REFRESH_INTERVAL_SECS: u64 = 15 * 60;
+
+thread_local! {
+    // Guards against a new run starting while the previous one still awaits calls.
+    static STATS_RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_stats_timer() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(STATS_REFRESH_INTERVAL_SECS), || {
+        ic_cdk::spawn(refresh_stats_task());
+    });
+}
+
+/// Refreshes the cached stats of every collection, then the launchpad totals.
+pub async fn refresh_stats_task() {
+    if STATS_RUNNING.with(|r| r.replace(true)) {
+        return;
+    }
+    let mut cursor = None;
+    while let Some(collection) = STATE.with(|s| s.borrow().next_collection(cursor)) {
+        cursor = Some(collection.canister_id);
+        let stats = collection_stats(collection.canister_id).await;
+        STATE.with(|s| s.borrow_mut().record_collection_stats(stats));
+    }
+    STATE.with(|s| s.borrow_mut().aggregate_collection_stats());
+    STATS_RUNNING.with(|r| r.set(false));
+}
+
+// The part of an `icrc3_get_blocks` result the stats read.
+#[derive(CandidType, Deserialize)]
+struct LogLength {
+    log_length: u128,
+}
+
+// Asks for no blocks, only the length of the collection's log, which is 0
+// before its first transaction.
+async fn transactions_of(canister_id: Principal) -> Result<u128, String> {
+    let call_result: Result<(LogLength,), _> =
+        ic_cdk::call(canister_id, "icrc3_get_blocks", (GetBlocksArgs::new(),)).await;
+    match call_result {
+        Ok((result,)) => Ok(result.log_length),
+        Err((code, msg)) => Err(format!(
+            "icrc3_get_blocks failed: Rejection Code: {:?}, Message: {:?}",
+            code, msg
+        )),
+    }
+}
+
+async fn collection_stats(canister_id: Principal) -> CollectionStats {
+    let (total_supply, holders, transactions, archives, cycles) = futures::join!(
+        query_collection::<u128>(canister_id, "icrc7_total_supply"),
+        query_collection::<u64>(canister_id, "holders_count"),
+        transactions_of(canister_id),
+        archives_of(canister_id),
+        wallet_balance(canister_id),
+    );
+    // The collection lists itself first as the holder of the latest blocks.
+    let archives = archives.map(|archives| {
+        archives
+            .iter()
+            .filter(|archive| archive.canister_id != canister_id)
+            .count() as u64
+    });
+    let error = [
+        total_supply.as_ref().err(),
+        holders.as_ref().err(),
+        transactions.as_ref().err(),
+        archives.as_ref().err(),
+        cycles.as_ref().err(),
+    ]
+    .into_iter()
+    .flatten()
+    .next()
+    .cloned();
+
+    CollectionStats {
+        canister_id,
+        total_supply: total_supply.ok(),
+        holders: holders.ok(),
+        transactions: transactions.ok(),
+        archives: archives.ok(),
+        cycles: cycles.ok(),
+        error,
+        refreshed_at: ic_cdk::api::time(),
+    }
+}
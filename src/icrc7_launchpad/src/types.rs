@@ -0,0 +1,1110 @@
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use icrc7_types::icrc37_types::InitApprovalsArg;
+use icrc7_types::icrc3_types::InitArchiveArg;
+use icrc7_types::icrc7_types::MintArg;
+use icrc_ledger_types::icrc1::account::Account;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use crate::{
+    deploy::{
+        COLLECTION_DEFAULT_CYCLES, COLLECTION_MAX_CYCLES, COLLECTION_MAX_TOP_UP_CYCLES,
+        COLLECTION_MIN_CYCLES, CREATOR_MAX_TOP_UP_CYCLES, SETTINGS_DEFAULT_MAX_FREEZING_THRESHOLD,
+        SETTINGS_DEFAULT_MAX_MEMORY_ALLOCATION, SETTINGS_DEFAULT_MAX_RESERVED_CYCLES_LIMIT,
+    },
+    monitor::{
+        MONITOR_DEFAULT_INTERVAL_SECS, MONITOR_DEFAULT_THRESHOLD_CYCLES,
+        MONITOR_DEFAULT_TOP_UP_CYCLES,
+    },
+    state::RESERVATION_DEFAULT_TTL_SECS,
+};
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArg {
+    pub minting_account: Option<Account>,
+    pub icrc7_symbol: String,
+    pub icrc7_name: String,
+    pub icrc7_description: Option<String>,
+    pub icrc7_logo: Option<String>,
+    pub icrc7_supply_cap: Option<u128>,
+    pub icrc7_max_query_batch_size: Option<u16>,
+    pub icrc7_max_update_batch_size: Option<u16>,
+    pub icrc7_max_take_value: Option<u128>,
+    pub icrc7_default_take_value: Option<u128>,
+    pub icrc7_max_memo_size: Option<u32>,
+    pub icrc7_atomic_batch_transfers: Option<bool>,
+    pub tx_window: Option<u64>,
+    pub permitted_drift: Option<u64>,
+    pub approval_init: Option<InitApprovalsArg>,
+    pub archive_init: Option<InitArchiveArg>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct Arg {
+    pub icrc7_symbol: String,
+    pub icrc7_name: String,
+    pub icrc7_description: Option<String>,
+    pub icrc7_logo: Option<String>,
+    pub icrc7_supply_cap: Option<u128>,
+    pub icrc7_max_query_batch_size: Option<u16>,
+    pub icrc7_max_update_batch_size: Option<u16>,
+    pub icrc7_max_take_value: Option<u128>,
+    pub icrc7_default_take_value: Option<u128>,
+    pub icrc7_max_memo_size: Option<u32>,
+    pub icrc7_atomic_batch_transfers: Option<bool>,
+    pub tx_window: Option<u64>,
+    pub permitted_drift: Option<u64>,
+    pub approval_init: Option<InitApprovalsArg>,
+    pub archive_init: Option<InitArchiveArg>,
+}
+
+impl From<(Account, Arg)> for InitArg {
+    fn from((account, arg): (Account, Arg)) -> Self {
+        Self {
+            minting_account: Some(account),
+            icrc7_symbol: arg.icrc7_symbol,
+            icrc7_name: arg.icrc7_name,
+            icrc7_description: arg.icrc7_description,
+            icrc7_logo: arg.icrc7_logo,
+            icrc7_supply_cap: arg.icrc7_supply_cap,
+            icrc7_max_query_batch_size: arg.icrc7_max_query_batch_size,
+            icrc7_max_update_batch_size: arg.icrc7_max_update_batch_size,
+            icrc7_max_take_value: arg.icrc7_max_take_value,
+            icrc7_default_take_value: arg.icrc7_default_take_value,
+            icrc7_max_memo_size: arg.icrc7_max_memo_size,
+            icrc7_atomic_batch_transfers: arg.icrc7_atomic_batch_transfers,
+            tx_window: arg.tx_window,
+            permitted_drift: arg.permitted_drift,
+            approval_init: arg.approval_init,
+            archive_init: arg.archive_init,
+        }
+    }
+}
+
+/// A rule broken by a collection init argument, `field` names the offending
+/// field using its Candid path.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ArgValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Search criteria for launchpad collections. `query` is a case-insensitive
+/// prefix of the collection name or symbol.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CollectionFilter {
+    pub query: Option<String>,
+    pub creator: Option<Principal>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+    pub featured: Option<bool>,
+    pub verified: Option<bool>,
+    /// Delisted collections are only returned when set to `true`.
+    pub include_delisted: Option<bool>,
+}
+
+/// A page of `search_collections`. The search stops after scanning a bounded
+/// number of collections, `next` is then passed back as `prev` to resume it.
+/// It is `None` once every collection has been scanned.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CollectionSearchPage {
+    pub collections: Vec<CollectionInfo>,
+    pub next: Option<Principal>,
+}
+
+/// Metadata read from a collection canister.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CollectionMetadata {
+    pub icrc7_symbol: String,
+    pub icrc7_name: String,
+    pub icrc7_description: Option<String>,
+    pub icrc7_logo: Option<String>,
+}
+
+/// Partial collection arguments. Templates store them as defaults and
+/// creators send them as overrides of a template.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ArgOverrides {
+    pub icrc7_symbol: Option<String>,
+    pub icrc7_name: Option<String>,
+    pub icrc7_description: Option<String>,
+    pub icrc7_logo: Option<String>,
+    pub icrc7_supply_cap: Option<u128>,
+    pub icrc7_max_query_batch_size: Option<u16>,
+    pub icrc7_max_update_batch_size: Option<u16>,
+    pub icrc7_max_take_value: Option<u128>,
+    pub icrc7_default_take_value: Option<u128>,
+    pub icrc7_max_memo_size: Option<u32>,
+    pub icrc7_atomic_batch_transfers: Option<bool>,
+    pub tx_window: Option<u64>,
+    pub permitted_drift: Option<u64>,
+    pub approval_init: Option<InitApprovalsArg>,
+    pub archive_init: Option<InitArchiveArg>,
+}
+
+impl ArgOverrides {
+    /// Applies `overrides` on top of `self`. Approval settings are merged
+    /// field by field, an archive config replaces the template's one.
+    pub fn merge(self, overrides: ArgOverrides) -> ArgOverrides {
+        let approval_init = match (self.approval_init, overrides.approval_init) {
+            (Some(defaults), Some(overrides)) => Some(InitApprovalsArg {
+                max_approvals: overrides.max_approvals.or(defaults.max_approvals),
+                max_approvals_per_token_or_collection: overrides
+                    .max_approvals_per_token_or_collection
+                    .or(defaults.max_approvals_per_token_or_collection),
+                max_revoke_approvals: overrides
+                    .max_revoke_approvals
+                    .or(defaults.max_revoke_approvals),
+                settle_to_approvals: overrides
+                    .settle_to_approvals
+                    .or(defaults.settle_to_approvals),
+                collection_approval_requires_token: overrides
+                    .collection_approval_requires_token
+                    .or(defaults.collection_approval_requires_token),
+            }),
+            (defaults, overrides) => overrides.or(defaults),
+        };
+        ArgOverrides {
+            icrc7_symbol: overrides.icrc7_symbol.or(self.icrc7_symbol),
+            icrc7_name: overrides.icrc7_name.or(self.icrc7_name),
+            icrc7_description: overrides.icrc7_description.or(self.icrc7_description),
+            icrc7_logo: overrides.icrc7_logo.or(self.icrc7_logo),
+            icrc7_supply_cap: overrides.icrc7_supply_cap.or(self.icrc7_supply_cap),
+            icrc7_max_query_batch_size: overrides
+                .icrc7_max_query_batch_size
+                .or(self.icrc7_max_query_batch_size),
+            icrc7_max_update_batch_size: overrides
+                .icrc7_max_update_batch_size
+                .or(self.icrc7_max_update_batch_size),
+            icrc7_max_take_value: overrides.icrc7_max_take_value.or(self.icrc7_max_take_value),
+            icrc7_default_take_value: overrides
+                .icrc7_default_take_value
+                .or(self.icrc7_default_take_value),
+            icrc7_max_memo_size: overrides.icrc7_max_memo_size.or(self.icrc7_max_memo_size),
+            icrc7_atomic_batch_transfers: overrides
+                .icrc7_atomic_batch_transfers
+                .or(self.icrc7_atomic_batch_transfers),
+            tx_window: overrides.tx_window.or(self.tx_window),
+            permitted_drift: overrides.permitted_drift.or(self.permitted_drift),
+            approval_init,
+            archive_init: overrides.archive_init.or(self.archive_init),
+        }
+    }
+}
+
+impl TryFrom<ArgOverrides> for Arg {
+    type Error = String;
+
+    fn try_from(arg: ArgOverrides) -> Result<Self, Self::Error> {
+        Ok(Self {
+            icrc7_symbol: arg
+                .icrc7_symbol
+                .ok_or_else(|| String::from("Missing icrc7_symbol"))?,
+            icrc7_name: arg
+                .icrc7_name
+                .ok_or_else(|| String::from("Missing icrc7_name"))?,
+            icrc7_description: arg.icrc7_description,
+            icrc7_logo: arg.icrc7_logo,
+            icrc7_supply_cap: arg.icrc7_supply_cap,
+            icrc7_max_query_batch_size: arg.icrc7_max_query_batch_size,
+            icrc7_max_update_batch_size: arg.icrc7_max_update_batch_size,
+            icrc7_max_take_value: arg.icrc7_max_take_value,
+            icrc7_default_take_value: arg.icrc7_default_take_value,
+            icrc7_max_memo_size: arg.icrc7_max_memo_size,
+            icrc7_atomic_batch_transfers: arg.icrc7_atomic_batch_transfers,
+            tx_window: arg.tx_window,
+            permitted_drift: arg.permitted_drift,
+            approval_init: arg.approval_init,
+            archive_init: arg.archive_init,
+        })
+    }
+}
+
+/// Named collection defaults registered by an admin.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub defaults: ArgOverrides,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for CollectionTemplate {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// A collection canister deployed by the launchpad.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionInfo {
+    pub canister_id: Principal,
+    pub creator: Principal,
+    pub icrc7_symbol: String,
+    pub icrc7_name: String,
+    pub created_at: u64,
+    pub wasm_version: String,
+    // `None` for collections registered before control was tracked, which
+    // kept the launchpad and the creator as controllers.
+    pub control: Option<ControlStatus>,
+    pub settings: Option<CollectionSettings>,
+    pub icrc7_description: Option<String>,
+    pub icrc7_logo: Option<String>,
+    pub metadata_refreshed_at: Option<u64>,
+    pub featured: Option<bool>,
+    pub verified: Option<bool>,
+    pub delisted: Option<Delisting>,
+}
+
+impl CollectionInfo {
+    pub fn is_featured(&self) -> bool {
+        self.featured.unwrap_or(false)
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verified.unwrap_or(false)
+    }
+
+    pub fn is_delisted(&self) -> bool {
+        self.delisted.is_some()
+    }
+
+    /// Whether the collection passes every criterion set in `filter`.
+    pub fn matches(&self, filter: &CollectionFilter) -> bool {
+        if let Some(query) = &filter.query {
+            let query = query.trim().to_lowercase();
+            if !self.icrc7_name.to_lowercase().starts_with(&query)
+                && !self.icrc7_symbol.to_lowercase().starts_with(&query)
+            {
+                return false;
+            }
+        }
+        filter.creator.is_none_or(|creator| creator == self.creator)
+            && filter
+                .created_after
+                .is_none_or(|created_after| self.created_at >= created_after)
+            && filter
+                .created_before
+                .is_none_or(|created_before| self.created_at < created_before)
+            && filter
+                .featured
+                .is_none_or(|featured| featured == self.is_featured())
+            && filter
+                .verified
+                .is_none_or(|verified| verified == self.is_verified())
+            && (filter.include_delisted.unwrap_or(false) || !self.is_delisted())
+    }
+
+    /// Whether the launchpad can still upgrade the collection.
+    pub fn is_managed(&self) -> bool {
+        matches!(self.control, None | Some(ControlStatus::Managed { .. }))
+    }
+}
+
+/// Why and by whom a collection was hidden from the launchpad listings.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Delisting {
+    pub reason: String,
+    pub moderator: Principal,
+    pub delisted_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ReportStatus {
+    Open,
+    Dismissed,
+    Actioned,
+}
+
+/// A user report against a collection, waiting in the review queue while open.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionReport {
+    pub id: u64,
+    pub canister_id: Principal,
+    pub reporter: Principal,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub created_at: u64,
+    pub resolved_by: Option<Principal>,
+    pub resolved_at: Option<u64>,
+}
+
+impl Storable for CollectionReport {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ModerationAction {
+    Delisted,
+    Relisted,
+    ReportDismissed { report_id: u64 },
+}
+
+/// An entry of the moderation log, kept for good in stable memory.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ModerationEntry {
+    pub id: u64,
+    pub canister_id: Principal,
+    pub action: ModerationAction,
+    pub reason: String,
+    pub moderator: Principal,
+    pub timestamp: u64,
+}
+
+impl Storable for ModerationEntry {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Controllers a creator asks for once the collection is verified. The
+/// launchpad always stays a controller until then to install the code.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ControllerPolicy {
+    CreatorOnly,
+    #[default]
+    CreatorAndLaunchpad,
+    Custom(Vec<Principal>),
+    Blackholed,
+}
+
+impl ControllerPolicy {
+    pub const MAX_CONTROLLERS: usize = 10;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let ControllerPolicy::Custom(controllers) = self {
+            if controllers.is_empty() {
+                return Err("Custom controllers must not be empty, use Blackholed instead".into());
+            }
+            if controllers.len() > Self::MAX_CONTROLLERS {
+                return Err(format!(
+                    "At most {} controllers are allowed",
+                    Self::MAX_CONTROLLERS
+                ));
+            }
+            if controllers.contains(&Principal::anonymous()) {
+                return Err("The anonymous principal cannot be a controller".into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn controllers(&self, launchpad: Principal, creator: Principal) -> Vec<Principal> {
+        match self {
+            ControllerPolicy::CreatorOnly => vec![creator],
+            ControllerPolicy::CreatorAndLaunchpad => vec![launchpad, creator],
+            ControllerPolicy::Custom(controllers) => {
+                let mut unique = vec![];
+                for controller in controllers {
+                    if !unique.contains(controller) {
+                        unique.push(*controller);
+                    }
+                }
+                unique
+            }
+            ControllerPolicy::Blackholed => vec![],
+        }
+    }
+
+    /// Checks the co-owners requested alongside this policy. Co-owners are
+    /// added to the controllers, so a blackholed collection cannot have any.
+    pub fn validate_co_owners(
+        &self,
+        launchpad: Principal,
+        creator: Principal,
+        co_owners: &[Principal],
+    ) -> Result<(), String> {
+        if co_owners.is_empty() {
+            return Ok(());
+        }
+        if *self == ControllerPolicy::Blackholed {
+            return Err("A blackholed collection cannot have co-owners".into());
+        }
+        if co_owners.contains(&Principal::anonymous()) {
+            return Err("The anonymous principal cannot be a co-owner".into());
+        }
+        if self.controllers_with(launchpad, creator, co_owners).len() > Self::MAX_CONTROLLERS {
+            return Err(format!(
+                "At most {} controllers are allowed",
+                Self::MAX_CONTROLLERS
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn controllers_with(
+        &self,
+        launchpad: Principal,
+        creator: Principal,
+        co_owners: &[Principal],
+    ) -> Vec<Principal> {
+        let mut controllers = self.controllers(launchpad, creator);
+        if *self != ControllerPolicy::Blackholed {
+            for co_owner in co_owners {
+                if !controllers.contains(co_owner) {
+                    controllers.push(*co_owner);
+                }
+            }
+        }
+        controllers
+    }
+}
+
+/// Who controls a registered collection canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ControlStatus {
+    /// The launchpad is one of the controllers and can upgrade the collection.
+    Managed { controllers: Vec<Principal> },
+    /// The launchpad removed itself, the listed principals control the collection.
+    Relinquished { controllers: Vec<Principal> },
+    /// The collection has no controllers and its code can never change.
+    Blackholed,
+}
+
+impl ControlStatus {
+    pub fn from_controllers(launchpad: Principal, controllers: Vec<Principal>) -> Self {
+        if controllers.is_empty() {
+            ControlStatus::Blackholed
+        } else if controllers.contains(&launchpad) {
+            ControlStatus::Managed { controllers }
+        } else {
+            ControlStatus::Relinquished { controllers }
+        }
+    }
+}
+
+impl Storable for CollectionInfo {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Fee charged in an ICRC-2 token for every collection deployment.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DeploymentFee {
+    pub ledger_id: Principal,
+    pub amount: u128,
+    pub treasury: Account,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum FeeTransferKind {
+    Refund,
+    Settlement,
+}
+
+/// An escrowed fee the launchpad could not send back to the payer, or on to
+/// the treasury, kept until an admin retries it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FailedFeeTransfer {
+    pub id: u64,
+    pub kind: FeeTransferKind,
+    pub fee: DeploymentFee,
+    pub to: Account,
+    pub error: String,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for FailedFeeTransfer {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Optional deployment settings passed alongside the collection `Arg`.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct DeployOptions {
+    pub wasm_version: Option<String>,
+    pub cycles: Option<u128>,
+    pub controllers: Option<ControllerPolicy>,
+    pub settings: Option<CollectionSettings>,
+    /// Minting authority of the collection, the caller's default account when
+    /// unset. Its owner must have called `accept_minting_role` for the caller.
+    pub minting_account: Option<Account>,
+    /// Principals added to the controllers once the collection is verified.
+    pub co_owners: Option<Vec<Principal>>,
+}
+
+/// Canister settings of a collection. Unset fields of a deploy request fall
+/// back to the system defaults.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CollectionSettings {
+    pub compute_allocation: Option<u64>,
+    pub memory_allocation: Option<u64>,
+    pub freezing_threshold: Option<u64>,
+    pub reserved_cycles_limit: Option<u128>,
+}
+
+/// Admin-set ceilings on the settings creators may request.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SettingsLimits {
+    pub max_compute_allocation: u64,
+    pub max_memory_allocation: u64,
+    pub max_freezing_threshold: u64,
+    pub max_reserved_cycles_limit: u128,
+}
+
+impl Default for SettingsLimits {
+    fn default() -> Self {
+        Self {
+            max_compute_allocation: 0,
+            max_memory_allocation: SETTINGS_DEFAULT_MAX_MEMORY_ALLOCATION,
+            max_freezing_threshold: SETTINGS_DEFAULT_MAX_FREEZING_THRESHOLD,
+            max_reserved_cycles_limit: SETTINGS_DEFAULT_MAX_RESERVED_CYCLES_LIMIT,
+        }
+    }
+}
+
+/// A collection WASM uploaded to the launchpad.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WasmVersion {
+    pub version: String,
+    pub size: u64,
+    pub chunk_count: u32,
+    pub sha256: Option<ByteBuf>,
+    pub committed: bool,
+    pub uploaded_at: u64,
+}
+
+impl Storable for WasmVersion {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WasmChunkKey {
+    pub version: String,
+    pub index: u32,
+}
+
+impl Storable for WasmChunkKey {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum UpgradeStatus {
+    Upgraded,
+    Failed { message: String },
+}
+
+/// The latest upgrade attempt made by the launchpad on a collection.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionUpgrade {
+    pub canister_id: Principal,
+    pub wasm_version: String,
+    pub status: UpgradeStatus,
+    pub updated_at: u64,
+}
+
+impl Storable for CollectionUpgrade {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Progress of an `upgrade_all_collections` run. Collections are visited in
+/// canister id order and `cursor` is the last one processed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UpgradeJob {
+    pub wasm_version: String,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub cursor: Option<Principal>,
+    pub upgraded: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DeploymentStatus {
+    Created,
+    Installed,
+    Verified,
+    Failed { message: String },
+    // The canister of a failed deployment is being uninstalled to go back to
+    // the spare pool; it can no longer be retried.
+    Reclaiming,
+}
+
+/// A collection deployment, tracked from canister creation until the
+/// installed collection has been verified.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Deployment {
+    pub canister_id: Principal,
+    pub creator: Principal,
+    pub icrc7_symbol: String,
+    pub icrc7_name: String,
+    pub wasm_version: String,
+    pub init_arg: ByteBuf,
+    pub controller_policy: Option<ControllerPolicy>,
+    pub settings: Option<CollectionSettings>,
+    pub co_owners: Option<Vec<Principal>>,
+    pub status: DeploymentStatus,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Deployment {
+    /// Moves a failed deployment to `Reclaiming` and returns the failed status,
+    /// to restore if the canister cannot be uninstalled.
+    pub fn start_reclaim(&mut self) -> Result<DeploymentStatus, String> {
+        match self.status {
+            DeploymentStatus::Failed { .. } => Ok(std::mem::replace(
+                &mut self.status,
+                DeploymentStatus::Reclaiming,
+            )),
+            _ => Err("Only failed deployments can be retried or reclaimed".into()),
+        }
+    }
+}
+
+impl Storable for Deployment {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Maximum number of deployments a creator may start within a rolling window.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DeploymentQuota {
+    pub max_deployments: u32,
+    pub window_secs: u64,
+}
+
+/// Tokens minted into a collection right after it is deployed, either given
+/// inline or uploaded beforehand with `upload_mint_manifest_chunk`.
+#[derive(CandidType, Deserialize, Clone)]
+pub enum MintManifest {
+    Inline(Vec<MintArg>),
+    Uploaded,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum MintJobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed { message: String },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MintFailure {
+    pub token_id: u128,
+    pub message: String,
+}
+
+/// Progress of minting a manifest into a freshly deployed collection.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MintJob {
+    pub canister_id: Principal,
+    pub creator: Principal,
+    pub total: u64,
+    pub minted: u64,
+    pub failed: u64,
+    pub failures: Vec<MintFailure>,
+    pub status: MintJobStatus,
+    pub minting_account: Option<Account>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for MintJob {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Figures read from one collection by the last stats refresh. A field is
+/// `None` when the collection could not report it, see `error`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollectionStats {
+    pub canister_id: Principal,
+    pub total_supply: Option<u128>,
+    pub holders: Option<u64>,
+    pub transactions: Option<u128>,
+    pub archives: Option<u64>,
+    pub cycles: Option<u128>,
+    pub error: Option<String>,
+    pub refreshed_at: u64,
+}
+
+impl Storable for CollectionStats {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Totals over every registered collection. Holders are counted per
+/// collection, an account holding tokens of two collections counts twice.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LaunchpadStats {
+    pub collections: u64,
+    pub unreachable: u64,
+    pub total_supply: u128,
+    pub holders: u64,
+    pub transactions: u128,
+    pub archives: u64,
+    pub cycles: u128,
+    pub refreshed_at: u64,
+}
+
+/// Launchpad-wide uniqueness of collection symbols and names.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReservationConfig {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+}
+
+impl Default for ReservationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: RESERVATION_DEFAULT_TTL_SECS,
+        }
+    }
+}
+
+/// A symbol or name held by a creator, ahead of deployment until
+/// `expires_at`, then for as long as `collection` stays listed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Reservation {
+    pub owner: Principal,
+    pub collection: Option<Principal>,
+    pub reserved_at: u64,
+    pub expires_at: Option<u64>,
+}
+
+impl Storable for Reservation {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Admin-set bounds on the cycles spent by the launchpad per collection.
+/// `max_creator_top_up_cycles` caps the total a creator can have topped up
+/// from the launchpad balance through `top_up_collection`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CyclesConfig {
+    pub default_cycles: u128,
+    pub min_cycles: u128,
+    pub max_cycles: u128,
+    pub max_top_up_cycles: u128,
+    pub max_creator_top_up_cycles: u128,
+}
+
+impl Default for CyclesConfig {
+    fn default() -> Self {
+        Self {
+            default_cycles: COLLECTION_DEFAULT_CYCLES,
+            min_cycles: COLLECTION_MIN_CYCLES,
+            max_cycles: COLLECTION_MAX_CYCLES,
+            max_top_up_cycles: COLLECTION_MAX_TOP_UP_CYCLES,
+            max_creator_top_up_cycles: CREATOR_MAX_TOP_UP_CYCLES,
+        }
+    }
+}
+
+/// Cycles the launchpad has spent on behalf of a creator.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CyclesUsage {
+    pub deployed: u128,
+    pub topped_up: u128,
+}
+
+impl Storable for CyclesUsage {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Admin-set policy for the periodic cycles check of collections and their archives.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MonitorConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub threshold_cycles: u128,
+    pub top_up_cycles: u128,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: MONITOR_DEFAULT_INTERVAL_SECS,
+            threshold_cycles: MONITOR_DEFAULT_THRESHOLD_CYCLES,
+            top_up_cycles: MONITOR_DEFAULT_TOP_UP_CYCLES,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CanisterKind {
+    Collection,
+    Archive,
+}
+
+/// Outcome of the last cycles check of a collection or archive canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CanisterHealth {
+    pub canister_id: Principal,
+    pub collection_id: Principal,
+    pub kind: CanisterKind,
+    pub balance: Option<u128>,
+    pub topped_up: u128,
+    pub error: Option<String>,
+    pub checked_at: u64,
+}
+
+impl Storable for CanisterHealth {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(Encode!(self).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_merge_on_top_of_template_defaults() {
+        let defaults = ArgOverrides {
+            icrc7_name: Some("Template".into()),
+            icrc7_supply_cap: Some(10_000),
+            approval_init: Some(InitApprovalsArg {
+                max_approvals: Some(10),
+                max_approvals_per_token_or_collection: Some(5),
+                max_revoke_approvals: None,
+                settle_to_approvals: None,
+                collection_approval_requires_token: None,
+            }),
+            ..Default::default()
+        };
+        let overrides = ArgOverrides {
+            icrc7_symbol: Some("PFP".into()),
+            icrc7_supply_cap: Some(500),
+            approval_init: Some(InitApprovalsArg {
+                max_approvals: Some(20),
+                max_approvals_per_token_or_collection: None,
+                max_revoke_approvals: None,
+                settle_to_approvals: None,
+                collection_approval_requires_token: Some(true),
+            }),
+            ..Default::default()
+        };
+
+        let arg = Arg::try_from(defaults.clone().merge(overrides)).unwrap();
+        assert_eq!(arg.icrc7_symbol, "PFP");
+        assert_eq!(arg.icrc7_name, "Template");
+        assert_eq!(arg.icrc7_supply_cap, Some(500));
+        let approval_init = arg.approval_init.unwrap();
+        assert_eq!(approval_init.max_approvals, Some(20));
+        assert_eq!(approval_init.max_approvals_per_token_or_collection, Some(5));
+        assert_eq!(approval_init.collection_approval_requires_token, Some(true));
+
+        assert!(Arg::try_from(defaults.merge(ArgOverrides::default())).is_err());
+    }
+
+    #[test]
+    fn collection_filter_matches_prefix_and_flags() {
+        let info = CollectionInfo {
+            canister_id: Principal::management_canister(),
+            creator: Principal::anonymous(),
+            icrc7_symbol: "ICFL".into(),
+            icrc7_name: "ICP Flower".into(),
+            created_at: 100,
+            wasm_version: "0.1.0".into(),
+            control: None,
+            settings: None,
+            icrc7_description: None,
+            icrc7_logo: None,
+            metadata_refreshed_at: None,
+            featured: Some(true),
+            verified: None,
+            delisted: None,
+        };
+        let filter = |query: &str| CollectionFilter {
+            query: Some(query.into()),
+            ..Default::default()
+        };
+
+        assert!(info.matches(&filter("icp f")));
+        assert!(info.matches(&filter("icf")));
+        assert!(!info.matches(&filter("flower")));
+        assert!(info.matches(&CollectionFilter {
+            featured: Some(true),
+            verified: Some(false),
+            created_after: Some(100),
+            ..Default::default()
+        }));
+        assert!(!info.matches(&CollectionFilter {
+            created_before: Some(100),
+            ..Default::default()
+        }));
+
+        let delisted = CollectionInfo {
+            delisted: Some(Delisting {
+                reason: "spam".into(),
+                moderator: Principal::anonymous(),
+                delisted_at: 200,
+            }),
+            ..info
+        };
+        assert!(!delisted.matches(&CollectionFilter::default()));
+        assert!(delisted.matches(&CollectionFilter {
+            include_delisted: Some(true),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn co_owners_join_the_requested_controllers() {
+        let launchpad = Principal::management_canister();
+        let creator = Principal::from_slice(&[1]);
+        let co_owner = Principal::from_slice(&[2]);
+
+        assert_eq!(
+            ControllerPolicy::CreatorOnly.controllers_with(
+                launchpad,
+                creator,
+                &[co_owner, creator]
+            ),
+            vec![creator, co_owner]
+        );
+        assert!(ControllerPolicy::CreatorOnly
+            .validate_co_owners(launchpad, creator, &[co_owner])
+            .is_ok());
+        assert!(ControllerPolicy::Blackholed
+            .validate_co_owners(launchpad, creator, &[co_owner])
+            .is_err());
+        let too_many: Vec<Principal> = (3..12).map(|i| Principal::from_slice(&[i])).collect();
+        assert!(ControllerPolicy::CreatorAndLaunchpad
+            .validate_co_owners(launchpad, creator, &too_many)
+            .is_err());
+    }
+
+    #[test]
+    fn only_failed_deployments_start_a_reclaim() {
+        let failed = DeploymentStatus::Failed {
+            message: "install failed".into(),
+        };
+        let mut deployment = Deployment {
+            canister_id: Principal::management_canister(),
+            creator: Principal::anonymous(),
+            icrc7_symbol: "ICFL".into(),
+            icrc7_name: "ICP Flower".into(),
+            wasm_version: "0.1.0".into(),
+            init_arg: ByteBuf::new(),
+            controller_policy: None,
+            settings: None,
+            co_owners: None,
+            status: failed.clone(),
+            attempts: 1,
+            created_at: 0,
+            updated_at: 0,
+        };
+
+        assert_eq!(deployment.start_reclaim(), Ok(failed));
+        assert_eq!(deployment.status, DeploymentStatus::Reclaiming);
+        // A second reclaim, like a retry, is refused while the first one runs.
+        assert!(deployment.start_reclaim().is_err());
+
+        deployment.status = DeploymentStatus::Created;
+        assert!(deployment.start_reclaim().is_err());
+        assert_eq!(deployment.status, DeploymentStatus::Created);
+    }
+}
@@ -0,0 +1,763 @@
+use candid::{Encode, Principal};
+use ic_cdk::api::management_canister::main::CanisterInstallMode;
+use ic_cdk_macros::update;
+use icrc7_types::{errors::DeployError, icrc7_types::MintArg};
+use icrc_ledger_types::icrc1::account::Account;
+use serde_bytes::ByteBuf;
+
+use crate::{
+    deploy::{
+        acquire_collection_canister, collection_controllers, fetch_collection_metadata,
+        run_deployment, set_collection_controllers, top_up_collection_canister,
+        uninstall_collection_canister, upgrade_collection_canister,
+    },
+    fee::{
+        charge_deployment_fee, refund_deployment_fee, retry_failed_fee_transfer,
+        settle_deployment_fee,
+    },
+    guards::{admin_guard, authenticated_guard, check_creator, controller_guard, creator_guard},
+    mint::set_mint_timer,
+    monitor::set_monitor_timer,
+    state::STATE,
+    stats::refresh_stats_task,
+    types::{
+        Arg, ArgOverrides, CollectionInfo, CollectionReport, CollectionTemplate, CollectionUpgrade,
+        ControlStatus, CyclesConfig, DeployOptions, Deployment, DeploymentFee, DeploymentQuota,
+        DeploymentStatus, InitArg, LaunchpadStats, MintJobStatus, MintManifest, MonitorConfig,
+        ReservationConfig, SettingsLimits, UpgradeJob, WasmVersion,
+    },
+    upgrade::set_upgrade_timer,
+    validation::{validate_arg, validate_mint_manifest},
+};
+
+#[update]
+pub async fn mint_collection_canister(
+    arg: Arg,
+    options: Option<DeployOptions>,
+) -> Result<Principal, DeployError> {
+    let caller = ic_cdk::caller();
+    check_creator(caller)?;
+    deploy_collection(caller, arg, options, &mut None).await
+}
+
+#[update]
+pub async fn mint_collection_canister_with_manifest(
+    arg: Arg,
+    manifest: MintManifest,
+    options: Option<DeployOptions>,
+) -> Result<Principal, DeployError> {
+    let caller = ic_cdk::caller();
+    check_creator(caller)?;
+    // An uploaded manifest is taken out of state before the first await, so
+    // that chunks uploaded during the deployment start the next manifest.
+    let (mut manifest, uploaded) = match manifest {
+        MintManifest::Inline(manifest) => (Some(manifest), false),
+        MintManifest::Uploaded => (
+            Some(STATE.with(|s| s.borrow_mut().take_mint_manifest(caller))),
+            true,
+        ),
+    };
+    let result = deploy_collection(caller, arg, options, &mut manifest).await;
+    // The deployment failed before queueing the manifest, give it back.
+    if let (Err(_), Some(manifest), true) = (&result, manifest, uploaded) {
+        STATE.with(|s| s.borrow_mut().restore_mint_manifest(caller, manifest));
+    }
+    result
+}
+
+#[update(guard = "creator_guard")]
+pub fn upload_mint_manifest_chunk(chunk: Vec<MintArg>) -> Result<u64, String> {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow_mut().upload_mint_manifest_chunk(caller, chunk))
+}
+
+#[update(guard = "authenticated_guard")]
+pub fn clear_mint_manifest() {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow_mut().clear_mint_manifest(caller));
+}
+
+#[update]
+pub async fn mint_collection_canister_from_template(
+    name: String,
+    overrides: Option<ArgOverrides>,
+    options: Option<DeployOptions>,
+) -> Result<Principal, DeployError> {
+    let caller = ic_cdk::caller();
+    check_creator(caller)?;
+    let template = STATE
+        .with(|s| s.borrow().get_collection_template(&name))
+        .ok_or_else(|| invalid_arg("name")(String::from("Unknown template")))?;
+    let arg = Arg::try_from(template.defaults.merge(overrides.unwrap_or_default()))
+        .map_err(invalid_arg("overrides"))?;
+    deploy_collection(caller, arg, options, &mut None).await
+}
+
+fn invalid_arg(field: &str) -> impl FnOnce(String) -> DeployError + '_ {
+    move |reason| DeployError::InvalidArg {
+        field: field.to_string(),
+        reason,
+    }
+}
+
+fn fee_error(message: String) -> DeployError {
+    DeployError::FeePaymentFailed { message }
+}
+
+fn reservation_error(message: String) -> DeployError {
+    DeployError::Reserved { message }
+}
+
+// The symbol and name newly reserved for a deployment, released again if its
+// fee is refunded. A pair the creator reserved beforehand stays reserved.
+type ReservedIdentity = (Option<String>, Option<String>);
+
+fn reserve_identity(
+    owner: Principal,
+    symbol: &str,
+    name: &str,
+) -> Result<ReservedIdentity, String> {
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        if !s.reservation_config.enabled {
+            return Ok((None, None));
+        }
+        let new_symbol = s.get_symbol_reservation(symbol).is_none();
+        let new_name = s.get_name_reservation(name).is_none();
+        s.reserve_collection_identity(owner, symbol, name)?;
+        Ok((
+            new_symbol.then(|| symbol.to_string()),
+            new_name.then(|| name.to_string()),
+        ))
+    })
+}
+
+fn check_identity(owner: Principal, symbol: &str, name: &str) -> Result<(), DeployError> {
+    STATE
+        .with(|s| {
+            let s = s.borrow();
+            if s.reservation_config.enabled {
+                s.check_collection_identity(owner, symbol, name)?;
+            }
+            Ok(())
+        })
+        .map_err(reservation_error)
+}
+
+async fn refund_deployment(
+    fee: Option<DeploymentFee>,
+    account: Account,
+    owner: Principal,
+    (symbol, name): ReservedIdentity,
+) {
+    STATE.with(|s| s.borrow_mut().release_reservation(owner, symbol, name));
+    refund_deployment_fee(fee, account).await;
+}
+
+// With a manifest, the launchpad stays the minting authority of the new
+// collection until every token of the manifest has been minted. The manifest
+// is taken out of `manifest` once it is queued for the new collection.
+async fn deploy_collection(
+    caller: Principal,
+    arg: Arg,
+    options: Option<DeployOptions>,
+    manifest: &mut Option<Vec<MintArg>>,
+) -> Result<Principal, DeployError> {
+    let mut errors = validate_arg(&arg);
+    if let Some(manifest) = manifest {
+        errors.extend(validate_mint_manifest(&arg, manifest));
+    }
+    // Only the first violation is returned, `validate_collection_arg` lists all of them.
+    if let Some(error) = errors.into_iter().next() {
+        return Err(DeployError::InvalidArg {
+            field: error.field,
+            reason: error.message,
+        });
+    }
+    let account = Account {
+        owner: caller,
+        subaccount: None,
+    };
+    let options = options.unwrap_or_default();
+    let controller_policy = options.controllers.unwrap_or_default();
+    controller_policy
+        .validate()
+        .map_err(invalid_arg("options.controllers"))?;
+    let (wasm_version, wasm_module) = STATE
+        .with(|s| s.borrow().wasm_module(options.wasm_version))
+        .map_err(invalid_arg("options.wasm_version"))?;
+    let cycles = STATE
+        .with(|s| s.borrow().deploy_cycles(options.cycles))
+        .map_err(invalid_arg("options.cycles"))?;
+    let settings = STATE
+        .with(|s| s.borrow().resolve_collection_settings(options.settings))
+        .map_err(invalid_arg("options.settings"))?;
+    let co_owners = options.co_owners.unwrap_or_default();
+    controller_policy
+        .validate_co_owners(ic_cdk::id(), caller, &co_owners)
+        .map_err(invalid_arg("options.co_owners"))?;
+    if let Some(minting_account) = options.minting_account {
+        if minting_account.owner == Principal::anonymous() {
+            return Err(invalid_arg("options.minting_account")(
+                "The anonymous principal cannot mint".into(),
+            ));
+        }
+        if !STATE.with(|s| {
+            s.borrow()
+                .has_minting_consent(minting_account.owner, caller)
+        }) {
+            return Err(invalid_arg("options.minting_account")(format!(
+                "{} has not accepted the minting role for {}",
+                minting_account.owner, caller
+            )));
+        }
+    }
+
+    // Refuse a reserved symbol or name before charging anything. They are
+    // only reserved for the caller once the fee has been paid.
+    check_identity(caller, &arg.icrc7_symbol, &arg.icrc7_name)?;
+
+    // Admins are not bound by the deployment quota.
+    if admin_guard().is_err() {
+        STATE
+            .with(|s| s.borrow_mut().reserve_deployment_slot(caller))
+            .map_err(|_| DeployError::QuotaExceeded)?;
+    }
+
+    // Charge the deployment fee into the launchpad's escrow account before spending any cycles.
+    let fee = charge_deployment_fee(account).await.map_err(fee_error)?;
+
+    let reserved = match reserve_identity(caller, &arg.icrc7_symbol, &arg.icrc7_name) {
+        Ok(reserved) => reserved,
+        Err(e) => {
+            refund_deployment_fee(fee, account).await;
+            return Err(reservation_error(e));
+        }
+    };
+
+    let canister_id = match acquire_collection_canister(caller, cycles, &settings).await {
+        Ok(canister_id) => canister_id,
+        Err(e) => {
+            refund_deployment(fee, account, caller, reserved).await;
+            return Err(e);
+        }
+    };
+
+    let minting_account = match manifest {
+        Some(_) => Account {
+            owner: ic_cdk::id(),
+            subaccount: None,
+        },
+        None => options.minting_account.unwrap_or(account),
+    };
+    let now = ic_cdk::api::time();
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        s.add_deployment(Deployment {
+            canister_id,
+            creator: caller,
+            icrc7_symbol: arg.icrc7_symbol.clone(),
+            icrc7_name: arg.icrc7_name.clone(),
+            wasm_version: wasm_version.clone(),
+            init_arg: ByteBuf::from(Encode!(&InitArg::from((minting_account, arg))).unwrap()),
+            controller_policy: Some(controller_policy),
+            settings: Some(settings),
+            co_owners: Some(co_owners),
+            status: DeploymentStatus::Created,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+        });
+        if let Some(manifest) = manifest.take() {
+            s.queue_mint_manifest(canister_id, caller, options.minting_account, manifest);
+        }
+    });
+
+    match run_deployment(
+        canister_id,
+        CanisterInstallMode::Install,
+        wasm_version,
+        wasm_module,
+    )
+    .await
+    {
+        Ok(()) => {
+            settle_deployment_fee(fee).await;
+            start_mint_job(canister_id);
+            Ok(canister_id)
+        }
+        Err(e) => {
+            refund_deployment(fee, account, caller, reserved).await;
+            Err(e)
+        }
+    }
+}
+
+// Starts minting the manifest queued with a deployment, if any.
+fn start_mint_job(canister_id: Principal) {
+    if STATE
+        .with(|s| s.borrow_mut().start_mint_job(&canister_id))
+        .is_ok()
+    {
+        set_mint_timer();
+    }
+}
+
+fn failed_deployment_of(caller: Principal, canister_id: &Principal) -> Result<Deployment, String> {
+    let deployment = STATE
+        .with(|s| s.borrow().get_deployment(canister_id))
+        .ok_or_else(|| String::from("Unknown deployment"))?;
+    if caller != deployment.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the deployment or an admin".into());
+    }
+    match deployment.status {
+        DeploymentStatus::Failed { .. } => Ok(deployment),
+        _ => Err("Only failed deployments can be retried or reclaimed".into()),
+    }
+}
+
+#[update]
+pub async fn retry_deployment(
+    canister_id: Principal,
+    options: Option<DeployOptions>,
+) -> Result<Principal, DeployError> {
+    let caller = ic_cdk::caller();
+    check_creator(caller)?;
+    let deployment = failed_deployment_of(caller, &canister_id)
+        .map_err(|message| DeployError::UnknownDeployment { message })?;
+    check_identity(
+        deployment.creator,
+        &deployment.icrc7_symbol,
+        &deployment.icrc7_name,
+    )?;
+    let options = options.unwrap_or_default();
+    let (wasm_version, wasm_module) = STATE
+        .with(|s| {
+            s.borrow()
+                .wasm_module(options.wasm_version.or(Some(deployment.wasm_version)))
+        })
+        .map_err(invalid_arg("options.wasm_version"))?;
+
+    let account = Account {
+        owner: caller,
+        subaccount: None,
+    };
+    // Leave the failed state before the first await, so that a concurrent retry
+    // or reclaim of the same deployment is refused.
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set_deployment_status(&canister_id, DeploymentStatus::Created)
+    });
+    let fee = match charge_deployment_fee(account).await {
+        Ok(fee) => fee,
+        Err(e) => {
+            STATE.with(|s| {
+                s.borrow_mut()
+                    .set_deployment_status(&canister_id, deployment.status)
+            });
+            return Err(fee_error(e));
+        }
+    };
+    let reserved = match reserve_identity(
+        deployment.creator,
+        &deployment.icrc7_symbol,
+        &deployment.icrc7_name,
+    ) {
+        Ok(reserved) => reserved,
+        Err(e) => {
+            STATE.with(|s| {
+                s.borrow_mut()
+                    .set_deployment_status(&canister_id, deployment.status)
+            });
+            refund_deployment_fee(fee, account).await;
+            return Err(reservation_error(e));
+        }
+    };
+
+    // Reinstall also covers canisters whose code was installed before verification failed.
+    match run_deployment(
+        canister_id,
+        CanisterInstallMode::Reinstall,
+        wasm_version,
+        wasm_module,
+    )
+    .await
+    {
+        Ok(()) => {
+            settle_deployment_fee(fee).await;
+            start_mint_job(canister_id);
+            Ok(canister_id)
+        }
+        Err(e) => {
+            refund_deployment(fee, account, deployment.creator, reserved).await;
+            Err(e)
+        }
+    }
+}
+
+#[update]
+pub async fn reclaim_canister(canister_id: Principal) -> Result<(), String> {
+    failed_deployment_of(ic_cdk::caller(), &canister_id)?;
+    // Leave the failed state before the uninstall, so that a concurrent retry
+    // cannot reinstall into a canister that is going back to the spare pool.
+    let failed = STATE.with(|s| s.borrow_mut().start_reclaim(&canister_id))?;
+    if let Err(e) = uninstall_collection_canister(canister_id).await {
+        STATE.with(|s| s.borrow_mut().set_deployment_status(&canister_id, failed));
+        return Err(e);
+    }
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        s.remove_deployment(&canister_id);
+        s.remove_mint_job(&canister_id);
+        s.add_spare_canister(canister_id);
+    });
+    Ok(())
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_deployment_fee(fee: Option<DeploymentFee>) -> bool {
+    STATE.with(|s| s.borrow_mut().deployment_fee = fee);
+    true
+}
+
+/// Sends a refund or settlement that failed out of escrow again, returns the
+/// ledger block of the transfer.
+#[update(guard = "admin_guard")]
+pub async fn retry_fee_transfer(id: u64) -> Result<u128, String> {
+    retry_failed_fee_transfer(id).await
+}
+
+#[update(guard = "admin_guard")]
+pub fn upload_wasm_chunk(version: String, chunk: ByteBuf) -> Result<u32, String> {
+    STATE.with(|s| s.borrow_mut().upload_wasm_chunk(version, chunk.into_vec()))
+}
+
+#[update(guard = "admin_guard")]
+pub fn commit_wasm_version(
+    version: String,
+    expected_sha256: Option<ByteBuf>,
+) -> Result<WasmVersion, String> {
+    STATE.with(|s| s.borrow_mut().commit_wasm_version(version, expected_sha256))
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_default_wasm_version(version: String) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_default_wasm_version(version))
+}
+
+#[update(guard = "admin_guard")]
+pub fn delete_wasm_version(version: String) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().delete_wasm_version(version))
+}
+
+#[update]
+pub async fn upgrade_collection(
+    canister_id: Principal,
+    version: Option<String>,
+) -> Result<CollectionUpgrade, String> {
+    let caller = ic_cdk::caller();
+    let collection = STATE
+        .with(|s| s.borrow().get_collection(&canister_id))
+        .ok_or_else(|| String::from("Unknown collection"))?;
+    if caller != collection.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the collection or an admin".into());
+    }
+    if !collection.is_managed() {
+        return Err("The launchpad is no longer a controller of the collection".into());
+    }
+    let (wasm_version, wasm_module) = STATE.with(|s| s.borrow().wasm_module(version))?;
+    let result = upgrade_collection_canister(canister_id, wasm_module).await;
+    let upgrade = STATE.with(|s| {
+        s.borrow_mut()
+            .record_upgrade(canister_id, wasm_version, result)
+    });
+    Ok(upgrade)
+}
+
+#[update(guard = "admin_guard")]
+pub fn upgrade_all_collections(version: Option<String>) -> Result<UpgradeJob, String> {
+    let job = STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        let wasm_version = s.resolve_wasm_version(version)?;
+        s.start_upgrade_job(wasm_version.version)
+    })?;
+    set_upgrade_timer();
+    Ok(job)
+}
+
+#[update(guard = "admin_guard")]
+pub fn cancel_upgrade_all_collections() -> bool {
+    STATE.with(|s| s.borrow_mut().finish_upgrade_job())
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_cycles_config(config: CyclesConfig) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_cycles_config(config))
+}
+
+#[update]
+pub async fn top_up_collection(canister_id: Principal, amount: u128) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let collection = STATE
+        .with(|s| s.borrow().get_collection(&canister_id))
+        .ok_or_else(|| String::from("Unknown collection"))?;
+    if caller != collection.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the collection or an admin".into());
+    }
+    let max_top_up_cycles = STATE.with(|s| s.borrow().cycles_config.max_top_up_cycles);
+    if amount == 0 || amount > max_top_up_cycles {
+        return Err(format!(
+            "Top-up amount must be between 1 and {}",
+            max_top_up_cycles
+        ));
+    }
+    if amount > ic_cdk::api::canister_balance128() {
+        return Err("Insufficient launchpad cycles balance".into());
+    }
+    // Admins are not bound by the creator's top-up budget. The cycles are
+    // counted before the deposit so that concurrent top-ups cannot overrun it.
+    let budgeted = admin_guard().is_err();
+    STATE.with(|s| {
+        s.borrow_mut()
+            .reserve_top_up_cycles(collection.creator, amount, budgeted)
+    })?;
+    if let Err(e) = top_up_collection_canister(canister_id, amount).await {
+        STATE.with(|s| {
+            s.borrow_mut()
+                .release_top_up_cycles(collection.creator, amount)
+        });
+        return Err(e);
+    }
+    STATE.with(|s| {
+        s.borrow_mut()
+            .log_top_up_cycles(collection.creator, canister_id, amount)
+    });
+    Ok(())
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_monitor_config(config: MonitorConfig) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_monitor_config(config))?;
+    set_monitor_timer();
+    Ok(())
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_collection_template(
+    name: String,
+    description: Option<String>,
+    defaults: ArgOverrides,
+) -> Result<CollectionTemplate, String> {
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set_collection_template(name, description, defaults)
+    })
+}
+
+#[update(guard = "admin_guard")]
+pub fn delete_collection_template(name: String) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().delete_collection_template(&name))
+}
+
+/// Removes the launchpad from the controllers of a collection, leaving it to
+/// its remaining controllers. The launchpad can no longer upgrade it afterwards.
+#[update]
+pub async fn relinquish_control(canister_id: Principal) -> Result<ControlStatus, String> {
+    let caller = ic_cdk::caller();
+    let collection = STATE
+        .with(|s| s.borrow().get_collection(&canister_id))
+        .ok_or_else(|| String::from("Unknown collection"))?;
+    if caller != collection.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the collection or an admin".into());
+    }
+    if !collection.is_managed() {
+        return Err("The launchpad is no longer a controller of the collection".into());
+    }
+
+    let launchpad = ic_cdk::id();
+    let controllers: Vec<Principal> = collection_controllers(canister_id)
+        .await?
+        .into_iter()
+        .filter(|controller| *controller != launchpad)
+        .collect();
+    if controllers.is_empty() {
+        return Err("The collection has no other controller to hand off to".into());
+    }
+    set_collection_controllers(canister_id, controllers.clone()).await?;
+
+    let control = ControlStatus::Relinquished { controllers };
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set_collection_control(&canister_id, control.clone())
+    });
+    Ok(control)
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_settings_limits(limits: SettingsLimits) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_settings_limits(limits))
+}
+
+#[update(guard = "controller_guard")]
+pub fn add_admin(principal: Principal) {
+    STATE.with(|s| s.borrow_mut().add_admin(principal))
+}
+
+#[update(guard = "controller_guard")]
+pub fn remove_admin(principal: Principal) -> bool {
+    STATE.with(|s| s.borrow_mut().remove_admin(&principal))
+}
+
+#[update(guard = "admin_guard")]
+pub fn pause_deployments(paused: bool) -> bool {
+    STATE.with(|s| s.borrow_mut().deployments_paused = paused);
+    true
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_allowlist_enabled(enabled: bool) -> bool {
+    STATE.with(|s| s.borrow_mut().allowlist_enabled = enabled);
+    true
+}
+
+#[update(guard = "admin_guard")]
+pub fn add_to_allowlist(principals: Vec<Principal>) -> bool {
+    STATE.with(|s| s.borrow_mut().add_to_allowlist(principals));
+    true
+}
+
+#[update(guard = "admin_guard")]
+pub fn remove_from_allowlist(principals: Vec<Principal>) -> bool {
+    STATE.with(|s| s.borrow_mut().remove_from_allowlist(principals));
+    true
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_deployment_quota(quota: Option<DeploymentQuota>) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_deployment_quota(quota))
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_reservation_config(config: ReservationConfig) -> Result<(), String> {
+    STATE.with(|s| s.borrow_mut().set_reservation_config(config))
+}
+
+/// Reserves a symbol and name ahead of deployment, returns when the
+/// reservation expires.
+#[update(guard = "creator_guard")]
+pub fn reserve_collection_identity(symbol: String, name: String) -> Result<u64, String> {
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        if !s.reservation_config.enabled {
+            return Err("Reservations are not enabled".into());
+        }
+        s.reserve_collection_identity(ic_cdk::caller(), &symbol, &name)
+    })
+}
+
+#[update(guard = "authenticated_guard")]
+pub fn release_reservation(symbol: Option<String>, name: Option<String>) -> bool {
+    STATE.with(|s| {
+        s.borrow_mut()
+            .release_reservation(ic_cdk::caller(), symbol, name)
+    });
+    true
+}
+
+#[update(guard = "admin_guard")]
+pub fn set_collection_flags(
+    canister_id: Principal,
+    featured: Option<bool>,
+    verified: Option<bool>,
+) -> Result<CollectionInfo, String> {
+    STATE.with(|s| {
+        s.borrow_mut()
+            .set_collection_flags(&canister_id, featured, verified)
+    })
+}
+
+#[update]
+pub async fn refresh_collection_metadata(canister_id: Principal) -> Result<CollectionInfo, String> {
+    let caller = ic_cdk::caller();
+    let collection = STATE
+        .with(|s| s.borrow().get_collection(&canister_id))
+        .ok_or_else(|| String::from("Unknown collection"))?;
+    if caller != collection.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the collection or an admin".into());
+    }
+    let metadata = fetch_collection_metadata(canister_id).await?;
+    STATE.with(|s| {
+        s.borrow_mut()
+            .update_collection_metadata(&canister_id, metadata)
+    })
+}
+
+#[update]
+pub fn resume_mint_job(canister_id: Principal) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let job = STATE
+        .with(|s| s.borrow().get_mint_job(&canister_id))
+        .ok_or_else(|| String::from("Unknown mint job"))?;
+    if caller != job.creator && admin_guard().is_err() {
+        return Err("The caller is not the creator of the mint job or an admin".into());
+    }
+    if !matches!(job.status, MintJobStatus::Failed { .. }) {
+        return Err("Only failed mint jobs can be resumed".into());
+    }
+    STATE.with(|s| s.borrow_mut().start_mint_job(&canister_id))?;
+    set_mint_timer();
+    Ok(())
+}
+
+/// Refreshes the cached stats now instead of waiting for the timer.
+#[update(guard = "admin_guard")]
+pub async fn refresh_stats() -> LaunchpadStats {
+    refresh_stats_task().await;
+    STATE.with(|s| s.borrow().launchpad_stats.clone().unwrap_or_default())
+}
+
+/// Lets `deployer` deploy collections minted by the caller's accounts.
+#[update(guard = "authenticated_guard")]
+pub fn accept_minting_role(deployer: Principal) {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow_mut().accept_minting_role(caller, deployer));
+}
+
+#[update(guard = "authenticated_guard")]
+pub fn revoke_minting_role(deployer: Principal) -> bool {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow_mut().revoke_minting_role(caller, &deployer))
+}
+
+#[update(guard = "authenticated_guard")]
+pub fn report_collection(canister_id: Principal, reason: String) -> Result<u64, String> {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| {
+        s.borrow_mut()
+            .report_collection(caller, canister_id, reason)
+    })
+}
+
+#[update(guard = "admin_guard")]
+pub fn dismiss_report(report_id: u64, reason: String) -> Result<CollectionReport, String> {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| s.borrow_mut().dismiss_report(report_id, reason, caller))
+}
+
+#[update(guard = "admin_guard")]
+pub fn delist_collection(canister_id: Principal, reason: String) -> Result<CollectionInfo, String> {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| {
+        s.borrow_mut()
+            .delist_collection(canister_id, reason, caller)
+    })
+}
+
+#[update(guard = "admin_guard")]
+pub fn relist_collection(canister_id: Principal, reason: String) -> Result<CollectionInfo, String> {
+    let caller = ic_cdk::caller();
+    STATE.with(|s| {
+        s.borrow_mut()
+            .relist_collection(canister_id, reason, caller)
+    })
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::{deploy::upgrade_collection_canister, state::STATE};
+
+// Maximum number of collections visited by one timer run.
+pub const UPGRADE_BATCH_SIZE: usize = 10;
+
+// Instructions spent in one timer run after which the rest of the batch is
+// deferred to the next run, well below the per-message limit.
+pub const UPGRADE_INSTRUCTION_THRESHOLD: u64 = 10_000_000_000;
+
+pub fn set_upgrade_timer() {
+    ic_cdk_timers::set_timer(Duration::from_secs(1), || {
+        ic_cdk::spawn(upgrade_collections_task());
+    });
+}
+
+async fn upgrade_collections_task() {
+    let wasm_version = match STATE.with(|s| s.borrow().upgrade_job()) {
+        Some(job) if job.finished_at.is_none() => job.wasm_version,
+        _ => return,
+    };
+    let wasm_module = match STATE.with(|s| s.borrow().wasm_module(Some(wasm_version.clone()))) {
+        Ok((_, wasm_module)) => wasm_module,
+        Err(e) => {
+            ic_cdk::println!("upgrade_collections_task: {}", e);
+            STATE.with(|s| s.borrow_mut().finish_upgrade_job());
+            return;
+        }
+    };
+
+    for _ in 0..UPGRADE_BATCH_SIZE {
+        // Re-read the job on every step, it may have been cancelled meanwhile.
+        let cursor = match STATE.with(|s| s.borrow().upgrade_job()) {
+            Some(job) if job.finished_at.is_none() => job.cursor,
+            _ => return,
+        };
+        let collection = match STATE.with(|s| s.borrow().next_collection(cursor)) {
+            Some(collection) => collection,
+            None => {
+                STATE.with(|s| s.borrow_mut().finish_upgrade_job());
+                return;
+            }
+        };
+        // Collections the launchpad no longer controls cannot be upgraded.
+        if collection.wasm_version == wasm_version || !collection.is_managed() {
+            STATE.with(|s| {
+                s.borrow_mut()
+                    .advance_upgrade_job(collection.canister_id, None)
+            });
+            continue;
+        }
+
+        let result = upgrade_collection_canister(collection.canister_id, wasm_module.clone()).await;
+        STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            let upgrade = s.record_upgrade(collection.canister_id, wasm_version.clone(), result);
+            s.advance_upgrade_job(collection.canister_id, Some(&upgrade.status));
+        });
+
+        if ic_cdk::api::performance_counter(1) > UPGRADE_INSTRUCTION_THRESHOLD {
+            break;
+        }
+    }
+    set_upgrade_timer();
+}
@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+
+use icrc7_types::icrc7_types::MintArg;
+
+use crate::types::{Arg, ArgValidationError};
+
+pub const MAX_SYMBOL_LEN: usize = 32;
+pub const MAX_NAME_LEN: usize = 128;
+pub const MAX_DESCRIPTION_LEN: usize = 4096;
+pub const MAX_MINT_MANIFEST_LEN: usize = 10_000;
+
+// Defaults applied by the collection canister when a field is left unset.
+const COLLECTION_DEFAULT_MAX_TAKE_VALUE: u128 = 32;
+const COLLECTION_DEFAULT_MAX_APPROVALS_PER_TOKEN_OR_COLLECTION: u16 = 10000;
+const COLLECTION_DEFAULT_SETTLE_TO_APPROVALS: u16 = 9975;
+const COLLECTION_DEFAULT_MAX_MEMO_SIZE: u32 = 32;
+
+fn error(field: &str, message: impl Into<String>) -> ArgValidationError {
+    ArgValidationError {
+        field: field.to_string(),
+        message: message.into(),
+    }
+}
+
+fn check_text(
+    errors: &mut Vec<ArgValidationError>,
+    field: &str,
+    value: &str,
+    max_len: usize,
+    required: bool,
+) {
+    if required && value.trim().is_empty() {
+        errors.push(error(field, "must not be empty"));
+    } else if value.trim() != value {
+        errors.push(error(field, "must not start or end with whitespace"));
+    }
+    if value.len() > max_len {
+        errors.push(error(field, format!("must be at most {} bytes", max_len)));
+    }
+}
+
+fn check_non_zero(errors: &mut Vec<ArgValidationError>, field: &str, value: Option<u128>) {
+    if value == Some(0) {
+        errors.push(error(field, "must be greater than 0"));
+    }
+}
+
+/// Checks a collection init argument against the rules the collection
+/// canister relies on, returning every violation found.
+pub fn validate_arg(arg: &Arg) -> Vec<ArgValidationError> {
+    let mut errors = vec![];
+
+    check_text(
+        &mut errors,
+        "icrc7_symbol",
+        &arg.icrc7_symbol,
+        MAX_SYMBOL_LEN,
+        true,
+    );
+    check_text(
+        &mut errors,
+        "icrc7_name",
+        &arg.icrc7_name,
+        MAX_NAME_LEN,
+        true,
+    );
+    if let Some(description) = &arg.icrc7_description {
+        check_text(
+            &mut errors,
+            "icrc7_description",
+            description,
+            MAX_DESCRIPTION_LEN,
+            false,
+        );
+    }
+
+    check_non_zero(&mut errors, "icrc7_supply_cap", arg.icrc7_supply_cap);
+    check_non_zero(
+        &mut errors,
+        "icrc7_max_query_batch_size",
+        arg.icrc7_max_query_batch_size.map(u128::from),
+    );
+    check_non_zero(
+        &mut errors,
+        "icrc7_max_update_batch_size",
+        arg.icrc7_max_update_batch_size.map(u128::from),
+    );
+    check_non_zero(
+        &mut errors,
+        "icrc7_max_take_value",
+        arg.icrc7_max_take_value,
+    );
+    check_non_zero(
+        &mut errors,
+        "icrc7_default_take_value",
+        arg.icrc7_default_take_value,
+    );
+    let max_take_value = arg
+        .icrc7_max_take_value
+        .unwrap_or(COLLECTION_DEFAULT_MAX_TAKE_VALUE);
+    if let Some(default_take_value) = arg.icrc7_default_take_value {
+        if default_take_value > max_take_value {
+            errors.push(error(
+                "icrc7_default_take_value",
+                format!("must not exceed icrc7_max_take_value ({})", max_take_value),
+            ));
+        }
+    }
+
+    if let Some(approval_init) = &arg.approval_init {
+        check_non_zero(
+            &mut errors,
+            "approval_init.max_approvals",
+            approval_init.max_approvals.map(u128::from),
+        );
+        check_non_zero(
+            &mut errors,
+            "approval_init.max_approvals_per_token_or_collection",
+            approval_init
+                .max_approvals_per_token_or_collection
+                .map(u128::from),
+        );
+        let max_approvals_per_token_or_collection = approval_init
+            .max_approvals_per_token_or_collection
+            .unwrap_or(COLLECTION_DEFAULT_MAX_APPROVALS_PER_TOKEN_OR_COLLECTION);
+        let settle_to_approvals = approval_init
+            .settle_to_approvals
+            .unwrap_or(COLLECTION_DEFAULT_SETTLE_TO_APPROVALS);
+        if settle_to_approvals > max_approvals_per_token_or_collection {
+            errors.push(error(
+                "approval_init.settle_to_approvals",
+                format!(
+                    "must not exceed max_approvals_per_token_or_collection ({})",
+                    max_approvals_per_token_or_collection
+                ),
+            ));
+        }
+    }
+
+    if let Some(archive_init) = &arg.archive_init {
+        check_non_zero(
+            &mut errors,
+            "archive_init.archiveCycles",
+            Some(archive_init.archive_cycles),
+        );
+        check_non_zero(
+            &mut errors,
+            "archive_init.maxActiveRecords",
+            Some(archive_init.max_active_records),
+        );
+        check_non_zero(
+            &mut errors,
+            "archive_init.maxArchivePages",
+            Some(archive_init.max_archive_pages),
+        );
+        check_non_zero(
+            &mut errors,
+            "archive_init.maxRecordsInArchiveInstance",
+            Some(archive_init.max_records_in_archive_instance),
+        );
+        check_non_zero(
+            &mut errors,
+            "archive_init.maxRecordsToArchive",
+            Some(archive_init.max_records_to_archive),
+        );
+        if archive_init.settle_to_records > archive_init.max_active_records {
+            errors.push(error(
+                "archive_init.settleToRecords",
+                "must not exceed maxActiveRecords",
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Checks a mint manifest against the collection it will be minted into.
+/// Tokens are minted by the launchpad, so `from_subaccount` must be unset.
+pub fn validate_mint_manifest(arg: &Arg, manifest: &[MintArg]) -> Vec<ArgValidationError> {
+    let mut errors = vec![];
+
+    if manifest.is_empty() {
+        errors.push(error("manifest", "must not be empty"));
+    }
+    if manifest.len() > MAX_MINT_MANIFEST_LEN {
+        errors.push(error(
+            "manifest",
+            format!("must hold at most {} tokens", MAX_MINT_MANIFEST_LEN),
+        ));
+    }
+    if let Some(supply_cap) = arg.icrc7_supply_cap {
+        if manifest.len() as u128 > supply_cap {
+            errors.push(error(
+                "manifest",
+                format!("must not exceed icrc7_supply_cap ({})", supply_cap),
+            ));
+        }
+    }
+
+    let max_memo_size = arg
+        .icrc7_max_memo_size
+        .unwrap_or(COLLECTION_DEFAULT_MAX_MEMO_SIZE) as usize;
+    let mut token_ids = HashSet::new();
+    for (index, mint) in manifest.iter().enumerate() {
+        if mint.from_subaccount.is_some() {
+            errors.push(error(
+                &format!("manifest[{}].from_subaccount", index),
+                "must not be set",
+            ));
+        }
+        // Queued mints can run long after upload, past the collection's
+        // transaction window.
+        if mint.created_at_time.is_some() {
+            errors.push(error(
+                &format!("manifest[{}].created_at_time", index),
+                "must not be set",
+            ));
+        }
+        if mint
+            .memo
+            .as_ref()
+            .is_some_and(|memo| memo.len() > max_memo_size)
+        {
+            errors.push(error(
+                &format!("manifest[{}].memo", index),
+                format!("must be at most {} bytes", max_memo_size),
+            ));
+        }
+        if !token_ids.insert(mint.token_id) {
+            errors.push(error(
+                &format!("manifest[{}].token_id", index),
+                format!("duplicates token {}", mint.token_id),
+            ));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use icrc7_types::icrc3_types::{IndexType, InitArchiveArg};
+    use icrc_ledger_types::icrc1::account::Account;
+
+    fn arg() -> Arg {
+        Arg {
+            icrc7_symbol: "ICFL".into(),
+            icrc7_name: "ICP Flower".into(),
+            icrc7_description: None,
+            icrc7_logo: None,
+            icrc7_supply_cap: None,
+            icrc7_max_query_batch_size: None,
+            icrc7_max_update_batch_size: None,
+            icrc7_max_take_value: None,
+            icrc7_default_take_value: None,
+            icrc7_max_memo_size: None,
+            icrc7_atomic_batch_transfers: None,
+            tx_window: None,
+            permitted_drift: None,
+            approval_init: None,
+            archive_init: None,
+        }
+    }
+
+    fn fields(errors: Vec<ArgValidationError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.field).collect()
+    }
+
+    #[test]
+    fn accepts_minimal_arg() {
+        assert!(validate_arg(&arg()).is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let mut arg = arg();
+        arg.icrc7_symbol = "".into();
+        arg.icrc7_default_take_value = Some(64);
+        arg.archive_init = Some(InitArchiveArg {
+            archive_controllers: None,
+            archive_cycles: 2_000_000_000_000,
+            archive_index_type: IndexType::Stable,
+            max_active_records: 100,
+            max_archive_pages: 62500,
+            max_records_in_archive_instance: 10_000_000,
+            max_records_to_archive: 1000,
+            settle_to_records: 200,
+        });
+
+        assert_eq!(
+            fields(validate_arg(&arg)),
+            vec![
+                "icrc7_symbol",
+                "icrc7_default_take_value",
+                "archive_init.settleToRecords"
+            ]
+        );
+    }
+
+    fn mint(token_id: u128) -> MintArg {
+        MintArg {
+            from_subaccount: None,
+            to: Account {
+                owner: Principal::anonymous(),
+                subaccount: None,
+            },
+            token_id,
+            memo: None,
+            token_name: None,
+            token_description: None,
+            token_logo: None,
+            extra_data: None,
+            created_at_time: None,
+        }
+    }
+
+    #[test]
+    fn checks_mint_manifest() {
+        let mut arg = arg();
+        assert!(validate_mint_manifest(&arg, &[mint(1), mint(2)]).is_empty());
+
+        arg.icrc7_supply_cap = Some(2);
+        let mut manifest = vec![mint(1), mint(2), mint(1)];
+        manifest[1].from_subaccount = Some([1; 32]);
+        assert_eq!(
+            fields(validate_mint_manifest(&arg, &manifest)),
+            vec![
+                "manifest",
+                "manifest[1].from_subaccount",
+                "manifest[2].token_id"
+            ]
+        );
+    }
+}
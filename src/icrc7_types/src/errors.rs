@@ -1,4 +1,6 @@
-use candid::CandidType;
+use std::fmt;
+
+use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -17,16 +19,22 @@ pub enum TransferError {
 pub enum BurnError {
     Unauthorized,
     NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
 }
 
-#[derive(CandidType, Clone)]
+#[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum MintError {
     SupplyCapReached,
     Unauthorized,
     TokenIdAlreadyExist,
     TokenIdMinimumLimit,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
 }
@@ -98,4 +106,64 @@ pub enum TransferFromError {
     Duplicate { duplicate_of: u128 },
     GenericError { error_code: u128, message: String },
     GenericBatchError { error_code: u128, message: String },
-}
\ No newline at end of file
+}
+
+// Launchpad Error
+
+/// Why the launchpad could not deploy a collection. `code` is the
+/// `RejectionCode` returned by the management canister.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum DeployError {
+    AnonymousCaller,
+    Paused,
+    Unauthorized,
+    QuotaExceeded,
+    InvalidArg { field: String, reason: String },
+    InsufficientCycles,
+    CreateFailed { code: i32, msg: String },
+    InstallFailed { canister_id: Principal, code: i32, msg: String },
+    FeePaymentFailed { message: String },
+    Reserved { message: String },
+    UnknownDeployment { message: String },
+    VerificationFailed { canister_id: Principal, message: String },
+    GenericError { error_code: u128, message: String },
+}
+
+impl fmt::Display for DeployError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeployError::AnonymousCaller => write!(f, "anonymous user is not allowed"),
+            DeployError::Paused => write!(f, "Deployments are paused"),
+            DeployError::Unauthorized => {
+                write!(f, "The caller is not on the creator allowlist")
+            }
+            DeployError::QuotaExceeded => write!(f, "Deployment quota exceeded"),
+            DeployError::InvalidArg { field, reason } => {
+                write!(f, "Invalid collection arg: {}: {}", field, reason)
+            }
+            DeployError::InsufficientCycles => {
+                write!(f, "Insufficient launchpad cycles balance")
+            }
+            DeployError::CreateFailed { code, msg } => {
+                write!(f, "Canister creation failed: Code: {}, Message: {}", code, msg)
+            }
+            DeployError::InstallFailed {
+                canister_id,
+                code,
+                msg,
+            } => write!(
+                f,
+                "Deployment of {} failed: Code: {}, Message: {}",
+                canister_id, code, msg
+            ),
+            DeployError::FeePaymentFailed { message } => write!(f, "{}", message),
+            DeployError::Reserved { message } => write!(f, "{}", message),
+            DeployError::UnknownDeployment { message } => write!(f, "{}", message),
+            DeployError::VerificationFailed {
+                canister_id,
+                message,
+            } => write!(f, "Verification of {} failed: {}", canister_id, message),
+            DeployError::GenericError { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
@@ -204,7 +204,7 @@ impl Storable for CollectionApprovalAccount {
     const BOUND: Bound = Bound::Unbounded;
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct InitApprovalsArg {
     pub max_approvals: Option<u16>,
     pub max_approvals_per_token_or_collection: Option<u16>,
@@ -341,6 +341,8 @@ pub struct MintArg {
     pub token_name: Option<String>,
     pub token_description: Option<String>,
     pub token_logo: Option<String>,
+    pub extra_data: Option<Icrc7TokenMetadata>,
+    pub created_at_time: Option<u64>,
 }
 
 pub type MintResult = Result<u128, MintError>;
@@ -350,6 +352,7 @@ pub struct BurnArg {
     pub from_subaccount: Option<Subaccount>,
     pub token_id: u128,
     pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
 }
 
 pub type BurnResult = Result<u128, BurnError>;